@@ -0,0 +1,165 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex as SyncMutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use serde::Serialize;
+use tiny_http::{Request, Response};
+use tungstenite::{handshake::derive_accept_key, protocol::Role, Message, WebSocket};
+
+use crate::{
+    error::MediaError,
+    model::CurrentSession,
+    position::{now_ms, SessionPosition},
+    store::SessionStore,
+};
+
+use super::{error_response, header, request_header};
+
+// idle connections are pinged this often, which is also how closed ones are noticed
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+
+// the app emits more than this, e.g. settings_changed, which carries the api token and the
+// scrobbler credentials. only the session state goes out to the feed
+pub const FEED_EVENTS: [&str; 6] = [
+    "session_create",
+    "session_update",
+    "session_remove",
+    "current_session_change",
+    "current_session_remove",
+    "session_position",
+];
+
+// every message is {"event": <tauri event name>, "payload": <the same payload as the emit>}
+#[derive(Serialize)]
+struct FeedMessage<'a, T> {
+    event: &'a str,
+    payload: T,
+}
+
+// the first message on every connection, under the event name "snapshot"
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedSnapshot {
+    pub sessions: Vec<CurrentSession>,
+    pub active_session_id: Option<usize>,
+    // positions are only published when they change, so a paused one would never arrive
+    pub positions: Vec<SessionPosition>,
+}
+
+// fans the session events out to the connected websockets
+pub struct EventFeed {
+    store: Arc<SyncMutex<SessionStore>>,
+    subscribers: SyncMutex<Vec<Sender<Arc<str>>>>,
+}
+
+impl EventFeed {
+    pub fn new(store: Arc<SyncMutex<SessionStore>>) -> Self {
+        Self {
+            store,
+            subscribers: SyncMutex::new(Vec::new()),
+        }
+    }
+
+    // events outside of FEED_EVENTS are dropped
+    pub fn publish<S: Serialize>(&self, event: &str, payload: &S) {
+        if !FEED_EVENTS.contains(&event) {
+            return;
+        }
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return;
+        }
+        let message = match serde_json::to_string(&FeedMessage { event, payload }) {
+            Ok(m) => Arc::<str>::from(m),
+            Err(err) => {
+                println!("[feed] failed to serialize {event}: {err}");
+                return;
+            }
+        };
+        subscribers.retain(|s| s.send(Arc::clone(&message)).is_ok());
+    }
+
+    // the store is applied before an event is published, so a delta right after the snapshot
+    // can repeat what the snapshot already contains, but never misses anything
+    fn subscribe(&self) -> (String, Receiver<Arc<str>>) {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        let store = self.store.lock().unwrap();
+        let snapshot = FeedSnapshot {
+            sessions: store.current_sessions(),
+            active_session_id: store.active().map(|s| s.session_id),
+            positions: store.positions(now_ms()),
+        };
+        let message = serde_json::to_string(&FeedMessage {
+            event: "snapshot",
+            payload: snapshot,
+        })
+        .unwrap_or_default();
+        (message, rx)
+    }
+}
+
+pub fn is_upgrade(request: &Request) -> bool {
+    request_header(request, "Upgrade").is_some_and(|v| v.eq_ignore_ascii_case("websocket"))
+}
+
+pub fn accept(request: Request, feed: &EventFeed) {
+    let Some(key) = request_header(&request, "Sec-WebSocket-Key") else {
+        let response = error_response(
+            400,
+            MediaError::conversion_failed("Missing Sec-WebSocket-Key."),
+        );
+        if let Err(err) = request.respond(response) {
+            println!("[feed] {}", err);
+        }
+        return;
+    };
+    let response = Response::empty(101).with_header(header(
+        "Sec-WebSocket-Accept",
+        &derive_accept_key(key.as_bytes()),
+    ));
+    let (snapshot, rx) = feed.subscribe();
+    let stream = request.upgrade("websocket", response);
+    thread::spawn(move || {
+        let mut socket = WebSocket::from_raw_socket(stream, Role::Server, None);
+        if socket.send(Message::Text(snapshot)).is_err() {
+            return;
+        }
+        // only ever written to, whatever the client sends is ignored
+        loop {
+            let message = match rx.recv_timeout(PING_INTERVAL) {
+                Ok(m) => Message::Text(m.to_string()),
+                Err(RecvTimeoutError::Timeout) => Message::Ping(Vec::new()),
+                Err(RecvTimeoutError::Disconnected) => Message::Close(None),
+            };
+            let closing = matches!(message, Message::Close(_));
+            if socket.send(message).is_err() || closing {
+                return;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_publishes_session_events() {
+        let feed = EventFeed::new(Arc::default());
+        let (_, rx) = feed.subscribe();
+        feed.publish("settings_changed", &"secret");
+        feed.publish("session_remove", &1);
+
+        assert_eq!(
+            &*rx.try_recv().unwrap(),
+            r#"{"event":"session_remove","payload":1}"#
+        );
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    borrow::Cow,
+    io::{Cursor, Read},
+    net::{Ipv4Addr, SocketAddr},
+    sync::Arc,
+    thread::{self, JoinHandle},
+};
+
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::{
+    client::MediaClient,
+    error::{ErrorKind, MediaError},
+    model::{CurrentSession, SessionControl},
+    position::{now_ms, SessionPosition},
+    protocol::pick_thumbnail,
+};
+
+use feed::EventFeed;
+
+pub mod feed;
+pub mod overlay;
+
+// the websocket feed, see feed.rs
+pub const FEED_PATH: &str = "/events";
+
+// controls are tiny, anything bigger is not a control
+const MAX_BODY_SIZE: u64 = 64 * 1024;
+
+// the [api] table of the settings file, the api is off without it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiConfig {
+    pub port: u16,
+    // required as "Authorization: Bearer <token>" when set, or as ?token= where headers can not
+    // be set, like a websocket opened from a browser
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+// a session as the api returns it, with the position extrapolated to the time of the request
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiSession {
+    #[serde(flatten)]
+    pub session: CurrentSession,
+    pub position: Option<SessionPosition>,
+}
+
+type ApiResponse = Response<Cursor<Vec<u8>>>;
+
+// the server stops once this is dropped
+pub struct ApiServer {
+    server: Arc<Server>,
+    listener: Option<JoinHandle<()>>,
+}
+
+impl ApiServer {
+    // only listens on loopback, the api is meant for tools on the same machine
+    pub fn start(
+        handle: AppHandle,
+        config: ApiConfig,
+        feed: Arc<EventFeed>,
+    ) -> Result<Self, MediaError> {
+        let addr = SocketAddr::from((Ipv4Addr::LOCALHOST, config.port));
+        let server = match Server::http(addr) {
+            Ok(s) => Arc::new(s),
+            Err(err) => {
+                return Err(MediaError::service_failed(format!(
+                    "Failed to start the api on port {}.",
+                    config.port
+                ))
+                .with_source(err));
+            }
+        };
+        let listener = Arc::clone(&server);
+        let config = Arc::new(config);
+        // every request gets a thread of its own, so a slow client or control holds up no other
+        let listener = thread::spawn(move || {
+            for request in listener.incoming_requests() {
+                let handle = handle.clone();
+                let config = Arc::clone(&config);
+                let feed = Arc::clone(&feed);
+                thread::spawn(move || serve(&handle, &config, &feed, request));
+            }
+        });
+        println!("[api] listening on {addr}");
+        Ok(Self {
+            server,
+            listener: Some(listener),
+        })
+    }
+}
+
+fn serve(handle: &AppHandle, config: &ApiConfig, feed: &EventFeed, mut request: Request) {
+    let response = match authorize(&request, config) {
+        Err(response) => response,
+        Ok(()) if path_of(request.url()) == FEED_PATH => {
+            if feed::is_upgrade(&request) {
+                feed::accept(request, feed);
+                return;
+            }
+            error_response(
+                400,
+                MediaError::conversion_failed("Expected a websocket upgrade."),
+            )
+        }
+        Ok(()) => route(handle, &mut request),
+    };
+    if let Err(err) = request.respond(response) {
+        println!("[api] {}", err);
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        self.server.unblock();
+        // the port is only free again once the listener let go of the server, which a restart
+        // on the same port relies on
+        if let Some(listener) = self.listener.take() {
+            let _ = listener.join();
+        }
+    }
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).unwrap()
+}
+
+fn request_header<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .iter()
+        .find(|h| h.field.as_str().as_str().eq_ignore_ascii_case(name))
+        .map(|h| h.value.as_str())
+}
+
+fn json_response<T: Serialize>(status: u16, body: &T) -> ApiResponse {
+    Response::from_data(serde_json::to_vec(body).unwrap_or_default())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+}
+
+fn error_response(status: u16, err: MediaError) -> ApiResponse {
+    json_response(status, &err)
+}
+
+fn media_error_response(err: MediaError) -> ApiResponse {
+    let status = match err.kind {
+        ErrorKind::NotFound => 404,
+        ErrorKind::ControlRejected => 409,
+        ErrorKind::BackendUnavailable => 503,
+        _ => 500,
+    };
+    error_response(status, err)
+}
+
+fn path_of(url: &str) -> &str {
+    url.split_once('?').map_or(url, |(path, _)| path)
+}
+
+fn query_param<'a>(url: &'a str, name: &str) -> Option<&'a str> {
+    let (_, query) = url.split_once('?')?;
+    query.split('&').find_map(|p| {
+        let (key, value) = p.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+// dns rebinding gets around the same origin policy, so only requests addressed to this server by
+// its local name are served. browsers also let any page send simple posts to loopback, so
+// anything but a read has to come from a page served here. reads stay open to overlays hosted
+// elsewhere, the token is what keeps those private. websockets are not covered by the same origin
+// policy at all, so the feed is held to the same rule as a post
+fn check_host(request: &Request, port: u16) -> Result<(), ApiResponse> {
+    let is_local =
+        |host: &str| host == format!("127.0.0.1:{port}") || host == format!("localhost:{port}");
+    let host_ok = request_header(request, "Host").is_some_and(is_local);
+    let is_read = *request.method() == Method::Get && !feed::is_upgrade(request);
+    let origin_ok = match request_header(request, "Origin") {
+        _ if is_read => true,
+        Some(origin) => origin.strip_prefix("http://").is_some_and(is_local),
+        None => true,
+    };
+    if host_ok && origin_ok {
+        Ok(())
+    } else {
+        Err(error_response(
+            403,
+            MediaError::control_rejected("Requests must come from this machine."),
+        ))
+    }
+}
+
+fn check_token(request: &Request, token: Option<&str>) -> Result<(), ApiResponse> {
+    let Some(token) = token else {
+        return Ok(());
+    };
+    // browsers encode the query, so a token with reserved characters arrives percent-encoded
+    let provided = request_header(request, "Authorization")
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(Cow::Borrowed)
+        .or_else(|| {
+            query_param(request.url(), "token").map(|v| percent_decode_str(v).decode_utf8_lossy())
+        });
+    let authorized = provided.is_some_and(|v| tokens_match(v.trim(), token));
+    if authorized {
+        Ok(())
+    } else {
+        Err(
+            error_response(401, MediaError::control_rejected("Missing or wrong token."))
+                .with_header(header("WWW-Authenticate", "Bearer")),
+        )
+    }
+}
+
+// takes as long for a wrong token as for a right one, so the time of a failed request gives away
+// nothing about how much of the token it got right
+fn tokens_match(provided: &str, token: &str) -> bool {
+    let (provided, token) = (provided.as_bytes(), token.as_bytes());
+    provided.len() == token.len()
+        && provided
+            .iter()
+            .zip(token)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+fn authorize(request: &Request, config: &ApiConfig) -> Result<(), ApiResponse> {
+    check_host(request, config.port)?;
+    check_token(request, config.token.as_deref())
+}
+
+fn route(handle: &AppHandle, request: &mut Request) -> ApiResponse {
+    let url = request.url().to_string();
+    let path = path_of(&url);
+    let segments = path.trim_matches('/').split('/').collect::<Vec<_>>();
+    let method = request.method().clone();
+    let client = handle.state::<MediaClient>();
+
+    let session_id = match segments.as_slice() {
+        ["sessions", id, ..] => match id.parse::<usize>() {
+            Ok(id) => Some(id),
+            Err(_) => {
+                return error_response(
+                    404,
+                    MediaError::not_found(format!("Session {id} does not exist.")),
+                );
+            }
+        },
+        _ => None,
+    };
+
+    match (&method, segments.as_slice(), session_id) {
+        (Method::Get, ["sessions"], _) => {
+            let sessions = client
+                .get_current_sessions()
+                .into_iter()
+                .map(|s| api_session(&client, s))
+                .collect::<Vec<_>>();
+            json_response(200, &sessions)
+        }
+        (Method::Get, ["sessions", _], Some(id)) => match client.get_session(id) {
+            Ok(s) => json_response(200, &api_session(&client, s)),
+            Err(err) => media_error_response(err),
+        },
+        (Method::Get, ["sessions", _, "art"], Some(id)) => {
+            let size = query_param(&url, "size").and_then(|s| s.parse::<u32>().ok());
+            art_response(&client, id, size)
+        }
+        (Method::Post, ["sessions", _, "control"], Some(id)) => {
+            let mut body = String::new();
+            if let Err(err) = request
+                .as_reader()
+                .take(MAX_BODY_SIZE)
+                .read_to_string(&mut body)
+            {
+                return error_response(
+                    400,
+                    MediaError::conversion_failed("Failed to read the request body.")
+                        .with_source(err),
+                );
+            }
+            let control = match serde_json::from_str::<SessionControl>(&body) {
+                Ok(c) => c,
+                Err(err) => {
+                    return error_response(
+                        400,
+                        MediaError::conversion_failed("Invalid session control.").with_source(err),
+                    );
+                }
+            };
+            match client.control_session(id, control) {
+                Ok(()) => Response::from_data(Vec::new()).with_status_code(204),
+                Err(err) => media_error_response(err),
+            }
+        }
+        (Method::Get, ["overlay", name], _) => match overlay::overlay_response(name) {
+            Some(response) => response,
+            None => error_response(
+                404,
+                MediaError::not_found(format!("Overlay {name} does not exist.")),
+            ),
+        },
+        (_, ["sessions"] | ["sessions", _] | ["sessions", _, "art" | "control"], _) => {
+            error_response(
+                405,
+                MediaError::control_rejected(format!("{method} is not allowed here.")),
+            )
+        }
+        _ => error_response(
+            404,
+            MediaError::not_found(format!("{path} does not exist.")),
+        ),
+    }
+}
+
+pub(crate) fn api_session(client: &MediaClient, session: CurrentSession) -> ApiSession {
+    ApiSession {
+        position: client
+            .store
+            .lock()
+            .unwrap()
+            .position(session.session_id, now_ms()),
+        session,
+    }
+}
+
+fn art_response(client: &MediaClient, session_id: usize, size: Option<u32>) -> ApiResponse {
+    let session = match client.get_session(session_id) {
+        Ok(s) => s,
+        Err(err) => return media_error_response(err),
+    };
+    let artwork = session
+        .art_id
+        .as_deref()
+        .and_then(|id| client.artwork.get(id));
+    let Some(thumbnail) = artwork
+        .as_ref()
+        .and_then(|a| pick_thumbnail(&a.thumbnails, size))
+    else {
+        return error_response(
+            404,
+            MediaError::not_found(format!("Session {session_id} has no art."))
+                .with_session(session_id),
+        );
+    };
+    let etag = format!(
+        "\"{}-{}\"",
+        session.art_id.unwrap_or_default(),
+        thumbnail.size
+    );
+    Response::from_data(thumbnail.data.clone())
+        .with_header(header("Content-Type", &thumbnail.mime_type))
+        .with_header(header("Cache-Control", "no-cache"))
+        .with_header(header("ETag", &etag))
+}
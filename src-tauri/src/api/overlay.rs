@@ -0,0 +1,33 @@
+use tiny_http::Response;
+
+use super::{header, ApiResponse};
+
+// browser sources for obs, served at /overlay/<name>. every option is in the query string:
+// theme=dark|light|art, font=<css font family>, session=active|<id>|<source>, hidePaused=1
+// and token=<api token>
+const OVERLAY_CSS: &str = include_str!("overlay/overlay.css");
+const OVERLAY_JS: &str = include_str!("overlay/overlay.js");
+
+const OVERLAYS: [(&str, &str); 2] = [
+    ("compact", include_str!("overlay/compact.html")),
+    ("card", include_str!("overlay/card.html")),
+];
+
+// the pages are served as a single document, obs caches separate files too eagerly
+pub fn overlay_response(name: &str) -> Option<ApiResponse> {
+    let (_, page) = OVERLAYS.iter().find(|(n, _)| *n == name)?;
+    let page = page
+        .replace(
+            "<!-- overlay.css -->",
+            &format!("<style>\n{OVERLAY_CSS}</style>"),
+        )
+        .replace(
+            "<!-- overlay.js -->",
+            &format!("<script>\n{OVERLAY_JS}</script>"),
+        );
+    Some(
+        Response::from_data(page.into_bytes())
+            .with_header(header("Content-Type", "text/html; charset=utf-8"))
+            .with_header(header("Cache-Control", "no-cache")),
+    )
+}
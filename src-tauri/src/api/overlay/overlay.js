@@ -0,0 +1,150 @@
+// renders the followed session from the websocket feed, shared by every overlay layout
+(() => {
+  const TICKS_PER_MS = 10000;
+  const ART_SIZE = 300;
+  const RECONNECT_MS = 2000;
+
+  const params = new URLSearchParams(location.search);
+  const token = params.get('token');
+  // "active", a session id, or part of a source name such as "spotify"
+  const follow = (params.get('session') || 'active').toLowerCase();
+  // "dark", "light" or "art", which takes the colors from the album art
+  const theme = params.get('theme') || 'dark';
+  const font = params.get('font');
+  const hidePaused = params.get('hidePaused') === '1' || params.get('hidePaused') === 'true';
+
+  const root = document.querySelector('.overlay');
+  const art = root.querySelector('.art');
+  const title = root.querySelector('.title');
+  const artist = root.querySelector('.artist');
+  const album = root.querySelector('.album');
+  const bar = root.querySelector('.progress-bar');
+  const elapsed = root.querySelector('.elapsed');
+  const length = root.querySelector('.length');
+
+  if (theme === 'light') document.documentElement.classList.add('theme-light');
+  if (font) document.documentElement.style.setProperty('--overlay-font', font);
+
+  const sessions = new Map();
+  const positions = new Map();
+  let activeId = null;
+
+  const withToken = (path) => (token ? `${path}${path.includes('?') ? '&' : '?'}token=${encodeURIComponent(token)}` : path);
+
+  const formatTime = (ms) => {
+    const totalSecs = Math.max(Math.trunc(ms / 1000), 0);
+    const mins = Math.floor(totalSecs / 60);
+    const secs = totalSecs - mins * 60;
+    return `${mins}:${secs < 10 ? `0${secs}` : secs}`;
+  };
+
+  const isPlaying = (s) => s.session?.playback?.status === 'Playing';
+
+  const followed = () => {
+    const all = [...sessions.values()];
+    if (follow === 'active') {
+      return sessions.get(activeId) ?? all.find(isPlaying) ?? all[0];
+    }
+    const id = Number(follow);
+    if (Number.isInteger(id)) return sessions.get(id);
+    const matching = all.filter((s) => s.source.toLowerCase().includes(follow));
+    return matching.find(isPlaying) ?? matching[0];
+  };
+
+  const setPosition = (p) => positions.set(p.sessionId, { ...p, receivedAt: performance.now() });
+
+  const render = () => {
+    const s = followed();
+    const media = s?.session?.media;
+    root.classList.toggle('hidden', !media?.title || (hidePaused && !isPlaying(s)));
+    if (!s) return;
+
+    title.textContent = media?.title ?? '';
+    artist.textContent = media?.artist ?? '';
+    if (album) album.textContent = media?.album?.title ?? '';
+
+    const src = s.artId ? withToken(`/sessions/${s.sessionId}/art?size=${ART_SIZE}&v=${s.artId}`) : '';
+    if (art.getAttribute('src') !== src) {
+      if (src) art.setAttribute('src', src);
+      else art.removeAttribute('src');
+    }
+    art.classList.toggle('empty', !src);
+
+    const colors = theme === 'art' ? s.palette : undefined;
+    root.style.backgroundColor = colors?.dominant ?? '';
+    root.style.color = colors?.foreground ?? '';
+    bar.style.backgroundColor = colors?.accent ?? '';
+  };
+
+  // the feed only sends positions when they change, so they are extrapolated between messages
+  const tick = () => {
+    const s = followed();
+    const p = s && positions.get(s.sessionId);
+    if (p && p.end > p.start) {
+      const running = p.playing ? (performance.now() - p.receivedAt) * TICKS_PER_MS * p.rate : 0;
+      const position = Math.min(Math.max(p.position + running, p.start), p.end);
+      bar.style.width = `${((position - p.start) / (p.end - p.start)) * 100}%`;
+      if (elapsed) elapsed.textContent = formatTime((position - p.start) / TICKS_PER_MS);
+      if (length) length.textContent = formatTime((p.end - p.start) / TICKS_PER_MS);
+    } else {
+      bar.style.width = '0';
+      if (elapsed) elapsed.textContent = '';
+      if (length) length.textContent = '';
+    }
+    requestAnimationFrame(tick);
+  };
+
+  const handle = ({ event, payload }) => {
+    switch (event) {
+      case 'snapshot':
+        sessions.clear();
+        positions.clear();
+        payload.sessions.forEach((s) => sessions.set(s.sessionId, s));
+        payload.positions.forEach(setPosition);
+        activeId = payload.activeSessionId;
+        break;
+      case 'session_create':
+        sessions.set(payload.sessionId, { sessionId: payload.sessionId, source: payload.source });
+        break;
+      case 'session_update': {
+        const s = sessions.get(payload.sessionId);
+        if (!s) return;
+        s.session = payload.sessionModel;
+        // updates without art keep the previous art
+        if (payload.artId) {
+          s.artId = payload.artId;
+          s.palette = payload.palette;
+        }
+        break;
+      }
+      case 'session_remove':
+        sessions.delete(payload.sessionId);
+        positions.delete(payload.sessionId);
+        break;
+      case 'current_session_change':
+        activeId = payload.sessionId;
+        break;
+      case 'current_session_remove':
+        activeId = null;
+        break;
+      case 'session_position':
+        setPosition(payload);
+        return;
+      default:
+        return;
+    }
+    render();
+  };
+
+  const connect = () => {
+    const ws = new WebSocket(withToken(`${location.protocol === 'https:' ? 'wss' : 'ws'}://${location.host}/events`));
+    ws.onmessage = (e) => handle(JSON.parse(e.data));
+    ws.onclose = () => {
+      root.classList.add('hidden');
+      setTimeout(connect, RECONNECT_MS);
+    };
+  };
+
+  connect();
+  requestAnimationFrame(tick);
+})();
@@ -0,0 +1,211 @@
+use std::{
+    fs::{self, File},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex as SyncMutex},
+    time::SystemTime,
+};
+
+use image::ImageFormat;
+use lru::LruCache;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    error::MediaError,
+    model::{Artwork, Palette, Thumbnail},
+    thumbnail::{process_thumbnail, ThumbnailOptions},
+};
+
+pub const ARTWORK_DIR_NAME: &str = "artwork";
+
+const MEMORY_CAPACITY: usize = 64;
+const DISK_CAPACITY: usize = 512;
+// written for every entry, its modification time doubles as the last use of the entry
+const PALETTE_FILE_NAME: &str = "palette.json";
+
+// ids are the hex encoded SHA-256 of the raw art, before any processing
+pub fn art_id(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// art ids come from the frontend as well, so they are checked before being used as a path
+pub fn is_art_id(art_id: &str) -> bool {
+    art_id.len() == 64 && art_id.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+pub struct ArtworkCache {
+    options: SyncMutex<ThumbnailOptions>,
+    memory: SyncMutex<LruCache<String, Arc<Artwork>>>,
+    dir: Option<PathBuf>,
+}
+
+impl ArtworkCache {
+    pub fn new(options: ThumbnailOptions) -> Self {
+        Self {
+            options: SyncMutex::new(options),
+            memory: SyncMutex::new(LruCache::new(NonZeroUsize::new(MEMORY_CAPACITY).unwrap())),
+            dir: None,
+        }
+    }
+
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    pub fn options(&self) -> ThumbnailOptions {
+        self.options.lock().unwrap().clone()
+    }
+
+    // only art processed from now on is rendered with these, cached art keeps its sizes and is
+    // served at the closest one
+    pub fn set_options(&self, options: ThumbnailOptions) {
+        *self.options.lock().unwrap() = options;
+    }
+
+    pub fn get(&self, art_id: &str) -> Option<Arc<Artwork>> {
+        if !is_art_id(art_id) {
+            return None;
+        }
+        if let Some(artwork) = self.memory.lock().unwrap().get(art_id) {
+            return Some(Arc::clone(artwork));
+        }
+        let artwork = Arc::new(self.read_from_disk(art_id)?);
+        self.memory
+            .lock()
+            .unwrap()
+            .put(art_id.to_string(), Arc::clone(&artwork));
+        Some(artwork)
+    }
+
+    // identical art is only processed once, no matter how often a backend sends it
+    pub fn insert(&self, data: &[u8]) -> Result<Arc<Artwork>, MediaError> {
+        let art_id = art_id(data);
+        if let Some(artwork) = self.get(&art_id) {
+            return Ok(artwork);
+        }
+        let processed = process_thumbnail(data, &self.options())?;
+        let artwork = Arc::new(Artwork {
+            art_id: art_id.clone(),
+            thumbnails: processed.thumbnails,
+            palette: processed.palette,
+        });
+        if let Err(err) = self.write_to_disk(&artwork) {
+            // the disk cache only saves work across restarts, so the art is still usable
+            println!("{}", err);
+        }
+        self.memory
+            .lock()
+            .unwrap()
+            .put(art_id, Arc::clone(&artwork));
+        Ok(artwork)
+    }
+
+    fn read_from_disk(&self, art_id: &str) -> Option<Artwork> {
+        let entry_dir = self.dir.as_ref()?.join(art_id);
+        let palette_path = entry_dir.join(PALETTE_FILE_NAME);
+        let palette =
+            serde_json::from_slice::<Option<Palette>>(&fs::read(&palette_path).ok()?).ok()?;
+
+        let mut thumbnails = Vec::new();
+        for entry in fs::read_dir(&entry_dir).ok()?.flatten() {
+            let path = entry.path();
+            let Some(size) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u32>().ok())
+            else {
+                continue;
+            };
+            let Some(format) = ImageFormat::from_path(&path).ok() else {
+                continue;
+            };
+            thumbnails.push(Thumbnail {
+                size,
+                mime_type: format.to_mime_type().to_string(),
+                data: fs::read(&path).ok()?,
+            });
+        }
+        if thumbnails.is_empty() {
+            return None;
+        }
+        thumbnails.sort_by_key(|t| t.size);
+        touch(&palette_path);
+
+        Some(Artwork {
+            art_id: art_id.to_string(),
+            thumbnails,
+            palette,
+        })
+    }
+
+    fn write_to_disk(&self, artwork: &Artwork) -> Result<(), MediaError> {
+        let Some(dir) = self.dir.as_ref() else {
+            return Ok(());
+        };
+        // written next to the final location and renamed, so a crash never leaves half an entry
+        let entry_dir = dir.join(&artwork.art_id);
+        let tmp_dir = dir.join(format!("{}.tmp", artwork.art_id));
+        let write = || -> std::io::Result<()> {
+            fs::create_dir_all(&tmp_dir)?;
+            for thumbnail in artwork.thumbnails.iter() {
+                let extension = ImageFormat::from_mime_type(&thumbnail.mime_type)
+                    .and_then(|f| f.extensions_str().first().copied())
+                    .unwrap_or("bin");
+                fs::write(
+                    tmp_dir.join(format!("{}.{extension}", thumbnail.size)),
+                    &thumbnail.data,
+                )?;
+            }
+            fs::write(
+                tmp_dir.join(PALETTE_FILE_NAME),
+                serde_json::to_vec(&artwork.palette)?,
+            )?;
+            if entry_dir.exists() {
+                fs::remove_dir_all(&entry_dir)?;
+            }
+            fs::rename(&tmp_dir, &entry_dir)
+        };
+        if let Err(err) = write() {
+            fs::remove_dir_all(&tmp_dir).ok();
+            return Err(
+                MediaError::thumbnail_failed("Failed to write artwork cache.").with_source(err),
+            );
+        }
+        prune(dir);
+        Ok(())
+    }
+}
+
+fn touch(path: &Path) {
+    if let Ok(file) = File::options().write(true).open(path) {
+        file.set_modified(SystemTime::now()).ok();
+    }
+}
+
+fn last_used(entry_dir: &Path) -> SystemTime {
+    fs::metadata(entry_dir.join(PALETTE_FILE_NAME))
+        .and_then(|m| m.modified())
+        .unwrap_or(SystemTime::UNIX_EPOCH)
+}
+
+// drops the least recently used entries once the disk cache is over capacity
+fn prune(dir: &Path) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut entries = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .map(|p| (last_used(&p), p))
+        .collect::<Vec<_>>();
+    if entries.len() <= DISK_CAPACITY {
+        return;
+    }
+    entries.sort_by_key(|(used, _)| *used);
+    let excess = entries.len() - DISK_CAPACITY;
+    for (_, path) in entries.into_iter().take(excess) {
+        fs::remove_dir_all(path).ok();
+    }
+}
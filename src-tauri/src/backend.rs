@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::Receiver;
+
+use crate::{
+    error::MediaError,
+    model::{ActiveSessionChange, SessionControl, SessionCreate, SessionRemove, SessionUpdate},
+};
+
+// updates dominate the stream, so boxing them would only add an allocation per event
+#[allow(clippy::large_enum_variant)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub enum BackendEvent {
+    SessionCreate(SessionCreate),
+    SessionUpdate(SessionUpdate),
+    SessionRemove(SessionRemove),
+    // None means there is no active session anymore
+    ActiveSessionChange(Option<ActiveSessionChange>),
+}
+
+pub trait MediaBackend: Send + Sync {
+    // session_id is the id announced by SessionCreate
+    fn control_session(&self, session_id: usize, control: SessionControl)
+        -> Result<(), MediaError>;
+
+    // the returned receiver yields events until the backend shuts down
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError>;
+}
+
+// points at a mock script to replay instead of talking to real players
+pub const MOCK_SCRIPT_ENV: &str = "NOW_PLAYING_MOCK_SCRIPT";
+
+pub fn default_backend() -> Result<Box<dyn MediaBackend>, MediaError> {
+    if let Ok(path) = std::env::var(MOCK_SCRIPT_ENV) {
+        let backend = crate::mock::media::MockBackend::from_file(path)?;
+        return Ok(Box::new(backend));
+    }
+    if let Ok(path) = std::env::var(crate::capture::replay::REPLAY_ENV) {
+        let backend = crate::capture::replay::ReplayBackend::from_env(path)?;
+        return Ok(Box::new(backend));
+    }
+    platform_backend()
+}
+
+#[cfg(windows)]
+fn platform_backend() -> Result<Box<dyn MediaBackend>, MediaError> {
+    let backend = crate::winrt::media::WinRTBackend::new()?;
+    Ok(Box::new(backend))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_backend() -> Result<Box<dyn MediaBackend>, MediaError> {
+    let backend = crate::mpris::media::MprisBackend::new()?;
+    Ok(Box::new(backend))
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn platform_backend() -> Result<Box<dyn MediaBackend>, MediaError> {
+    Err(MediaError::backend_unavailable(
+        "No media backend is available on this platform.",
+    ))
+}
+
+// stands in when no backend could be created, so that commands report why instead of panicking
+pub struct UnavailableBackend {
+    pub reason: String,
+}
+
+impl UnavailableBackend {
+    fn error(&self) -> MediaError {
+        MediaError::backend_unavailable(self.reason.clone())
+    }
+}
+
+impl MediaBackend for UnavailableBackend {
+    fn control_session(
+        &self,
+        _session_id: usize,
+        _control: SessionControl,
+    ) -> Result<(), MediaError> {
+        Err(self.error())
+    }
+
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        Err(self.error())
+    }
+}
@@ -0,0 +1,191 @@
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use now_playing_lib::{
+    api::ApiSession,
+    error::MediaError,
+    ipc::{self, IpcRequest, IpcResponse, IpcSessions},
+    model::SessionControl,
+    template::{Template, TemplateContext},
+};
+
+const DEFAULT_STATUS_FORMAT: &str = "{if artist}{artist} - {end}{title}";
+const LIST_FORMAT: &str = "{source}\t{status | default(\"-\")}\t{if artist}{artist} - {end}{title}";
+
+// TimelineModel positions are in 100ns ticks
+const TICKS_PER_SECOND: f64 = 10_000_000.0;
+
+/// Reads and controls the media sessions of the running now-playing app.
+#[derive(Parser)]
+#[command(name = "now-playing-cli", version)]
+struct Cli {
+    /// Session id to act on, instead of the active session
+    #[arg(long, global = true)]
+    session: Option<usize>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Prints the current track
+    Status {
+        /// Prints the session as json
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+        /// Template such as "{artist} - {title} ({position}/{duration})"
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Resumes playback
+    Play,
+    /// Pauses playback
+    Pause,
+    /// Plays or pauses
+    Toggle,
+    /// Skips to the next track
+    Next,
+    /// Goes back to the previous track
+    Prev,
+    /// Seeks to a position like 90 or 1:30, or by an offset like +10 or -0:30
+    Seek {
+        #[arg(allow_hyphen_values = true)]
+        position: String,
+    },
+    /// Prints every session, the active one marked with *
+    List {
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("{}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<(), MediaError> {
+    let control = match cli.command {
+        Command::Status { json, format } => {
+            let sessions = sessions()?;
+            let session = pick(&sessions, cli.session)?;
+            if json {
+                println!("{}", to_json(session)?);
+            } else {
+                let template = Template::parse(format.as_deref().unwrap_or(DEFAULT_STATUS_FORMAT))?;
+                println!("{}", render(&template, session));
+            }
+            return Ok(());
+        }
+        Command::List { json } => {
+            let sessions = sessions()?;
+            if json {
+                println!("{}", to_json(&sessions.sessions)?);
+                return Ok(());
+            }
+            let template = Template::parse(LIST_FORMAT)?;
+            for session in sessions.sessions.iter() {
+                let id = session.session.session_id;
+                let marker = if Some(id) == sessions.active_session_id {
+                    "*"
+                } else {
+                    " "
+                };
+                println!("{marker}{id}\t{}", render(&template, session));
+            }
+            return Ok(());
+        }
+        Command::Play => SessionControl::Play,
+        Command::Pause => SessionControl::Pause,
+        Command::Toggle => SessionControl::TogglePlayPause,
+        Command::Next => SessionControl::SkipNext,
+        Command::Prev => SessionControl::SkipPrevious,
+        Command::Seek { position } => seek_control(&position, cli.session)?,
+    };
+    match ipc::request(&IpcRequest::ControlSession {
+        session_id: cli.session,
+        control,
+    })? {
+        IpcResponse::Error(err) => Err(MediaError::new(err.kind, err.message)),
+        _ => Ok(()),
+    }
+}
+
+fn sessions() -> Result<IpcSessions, MediaError> {
+    match ipc::request(&IpcRequest::GetCurrentSessions)? {
+        IpcResponse::Sessions(s) => Ok(s),
+        IpcResponse::Error(err) => Err(MediaError::new(err.kind, err.message)),
+        IpcResponse::Done => Err(MediaError::conversion_failed("Unexpected response.")),
+    }
+}
+
+fn pick(sessions: &IpcSessions, session_id: Option<usize>) -> Result<&ApiSession, MediaError> {
+    let Some(session_id) = session_id.or(sessions.active_session_id) else {
+        return Err(MediaError::not_found("No session is active."));
+    };
+    match sessions
+        .sessions
+        .iter()
+        .find(|s| s.session.session_id == session_id)
+    {
+        Some(s) => Ok(s),
+        None => Err(MediaError::not_found(format!(
+            "Session {session_id} does not exist."
+        ))),
+    }
+}
+
+fn render(template: &Template, session: &ApiSession) -> String {
+    template.render(&TemplateContext::new(
+        &session.session.session,
+        session.position.as_ref(),
+    ))
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String, MediaError> {
+    match serde_json::to_string_pretty(value) {
+        Ok(j) => Ok(j),
+        Err(err) => {
+            Err(MediaError::conversion_failed("Failed to serialize the session.").with_source(err))
+        }
+    }
+}
+
+// "90", "1:30" and "1:02:03" are positions in the track, with a sign they are offsets
+fn seek_control(position: &str, session_id: Option<usize>) -> Result<SessionControl, MediaError> {
+    let invalid = || MediaError::conversion_failed(format!("Invalid position {position}."));
+    let (sign, time) = match position.as_bytes().first() {
+        Some(b'+') => (Some(1), &position[1..]),
+        Some(b'-') => (Some(-1), &position[1..]),
+        _ => (None, position),
+    };
+    let mut seconds = 0.0;
+    for part in time.split(':') {
+        let value = part.parse::<f64>().map_err(|_| invalid())?;
+        if !value.is_finite() || value < 0.0 {
+            return Err(invalid());
+        }
+        seconds = seconds * 60.0 + value;
+    }
+    let ticks = (seconds * TICKS_PER_SECOND) as i64;
+    match sign {
+        Some(sign) => Ok(SessionControl::SeekBy(sign * ticks)),
+        // positions count from the start of the timeline, which is not always 0
+        None => {
+            let sessions = sessions()?;
+            let start = pick(&sessions, session_id)?
+                .session
+                .session
+                .timeline
+                .as_ref()
+                .map_or(0, |t| t.start);
+            Ok(SessionControl::SeekTo(start + ticks))
+        }
+    }
+}
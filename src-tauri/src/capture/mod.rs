@@ -0,0 +1,102 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{backend::BackendEvent, error::MediaError};
+
+pub mod recorder;
+pub mod replay;
+
+// a capture is a directory holding the event log and one side file per thumbnail
+pub const CAPTURE_FILE_NAME: &str = "capture.jsonl";
+pub const THUMBNAIL_DIR_NAME: &str = "thumbnails";
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CaptureEntry {
+    // monotonic offset from the start of the recording
+    pub at_ms: u64,
+    // timeline timestamps are stored relative to when the event was recorded, see shift_timestamps
+    pub event: BackendEvent,
+    // thumbnail side file relative to the capture directory, stripped from the event itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thumbnail: Option<String>,
+}
+
+// timeline timestamps are wall clock times, which mean nothing once the capture is replayed later.
+// they are shifted back by the time of recording and forward by the time of replay, so a position
+// stays as old on replay as it was when recorded
+pub(crate) fn shift_timestamps(event: &mut BackendEvent, by_ms: i64) {
+    if let BackendEvent::SessionUpdate(update) = event {
+        if let Some(timeline) = update.session_model.timeline.as_mut() {
+            // 0 is no timestamp at all
+            if timeline.last_updated_at_ms > 0 {
+                timeline.last_updated_at_ms += by_ms;
+            }
+        }
+    }
+}
+
+// accepts either the capture directory or the capture file inside it
+fn resolve_capture_file(path: &Path) -> PathBuf {
+    if path.is_dir() {
+        path.join(CAPTURE_FILE_NAME)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+pub fn load_capture(path: impl AsRef<Path>) -> Result<Vec<(u64, BackendEvent)>, MediaError> {
+    let capture_file = resolve_capture_file(path.as_ref());
+    let capture_dir = capture_file.parent().unwrap_or(Path::new("."));
+    let file = match File::open(&capture_file) {
+        Ok(f) => f,
+        Err(err) => {
+            return Err(MediaError::backend_unavailable(format!(
+                "Failed to open capture {}.",
+                capture_file.display()
+            ))
+            .with_source(err));
+        }
+    };
+
+    let mut events = Vec::new();
+    for (line_number, line) in BufReader::new(file).lines().enumerate() {
+        let line = match line {
+            Ok(l) => l,
+            Err(err) => {
+                return Err(
+                    MediaError::backend_unavailable("Failed to read capture.").with_source(err)
+                );
+            }
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = match serde_json::from_str::<CaptureEntry>(&line) {
+            Ok(e) => e,
+            Err(err) => {
+                return Err(MediaError::backend_unavailable(format!(
+                    "Failed to parse capture line {}.",
+                    line_number + 1
+                ))
+                .with_source(err));
+            }
+        };
+        let mut event = entry.event;
+        if let (BackendEvent::SessionUpdate(update), Some(thumbnail)) =
+            (&mut event, entry.thumbnail)
+        {
+            match std::fs::read(capture_dir.join(&thumbnail)) {
+                Ok(data) => update.image = Some(data),
+                Err(err) => println!("Failed to read captured thumbnail {thumbnail}.\n{}", err),
+            }
+        }
+        events.push((entry.at_ms, event));
+    }
+    Ok(events)
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{backend::BackendEvent, error::MediaError, position::now_ms};
+
+use super::{shift_timestamps, CaptureEntry, CAPTURE_FILE_NAME, THUMBNAIL_DIR_NAME};
+
+// enables recording into the given directory
+pub const RECORD_DIR_ENV: &str = "NOW_PLAYING_RECORD_DIR";
+
+pub struct Recorder {
+    dir: PathBuf,
+    writer: BufWriter<File>,
+    started_at: Instant,
+    thumbnail_count: usize,
+}
+
+impl Recorder {
+    pub fn create(dir: impl AsRef<Path>) -> Result<Self, MediaError> {
+        let dir = dir.as_ref().to_path_buf();
+        if let Err(err) = fs::create_dir_all(dir.join(THUMBNAIL_DIR_NAME)) {
+            return Err(
+                MediaError::backend_unavailable("Failed to create capture directory.")
+                    .with_source(err),
+            );
+        }
+        let file = match File::create(dir.join(CAPTURE_FILE_NAME)) {
+            Ok(f) => f,
+            Err(err) => {
+                return Err(
+                    MediaError::backend_unavailable("Failed to create capture file.")
+                        .with_source(err),
+                );
+            }
+        };
+        Ok(Self {
+            dir,
+            writer: BufWriter::new(file),
+            started_at: Instant::now(),
+            thumbnail_count: 0,
+        })
+    }
+
+    pub fn from_env() -> Option<Self> {
+        let dir = std::env::var(RECORD_DIR_ENV).ok()?;
+        match Recorder::create(&dir) {
+            Ok(r) => {
+                println!("recording events to {dir}");
+                Some(r)
+            }
+            Err(err) => {
+                println!("{err}");
+                None
+            }
+        }
+    }
+
+    pub fn record(&mut self, event: &BackendEvent) -> Result<(), MediaError> {
+        let at_ms = self.started_at.elapsed().as_millis() as u64;
+        let mut event = event.clone();
+        shift_timestamps(&mut event, -now_ms());
+        let mut thumbnail = None;
+        if let BackendEvent::SessionUpdate(update) = &mut event {
+            if let Some(image) = update.image.take() {
+                let name = format!("{THUMBNAIL_DIR_NAME}/{}.bin", self.thumbnail_count);
+                if let Err(err) = fs::write(self.dir.join(&name), image) {
+                    return Err(MediaError::backend_unavailable(
+                        "Failed to write captured thumbnail.",
+                    )
+                    .with_source(err));
+                }
+                self.thumbnail_count += 1;
+                thumbnail = Some(name);
+            }
+        }
+
+        let entry = CaptureEntry {
+            at_ms,
+            event,
+            thumbnail,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(l) => l,
+            Err(err) => {
+                return Err(
+                    MediaError::backend_unavailable("Failed to serialize captured event.")
+                        .with_source(err),
+                );
+            }
+        };
+        // flushed per event so that a crash still leaves a usable capture behind
+        if let Err(err) = writeln!(self.writer, "{line}").and_then(|_| self.writer.flush()) {
+            return Err(
+                MediaError::backend_unavailable("Failed to write capture.").with_source(err)
+            );
+        }
+        Ok(())
+    }
+}
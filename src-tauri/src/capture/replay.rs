@@ -0,0 +1,84 @@
+use std::{path::Path, time::Duration};
+
+use tauri::async_runtime::{channel, Receiver, Sender};
+use tokio::time::{sleep_until, Instant};
+
+use crate::{
+    backend::{BackendEvent, MediaBackend},
+    error::MediaError,
+    model::SessionControl,
+    position::now_ms,
+};
+
+use super::{load_capture, shift_timestamps};
+
+// points at a capture directory to replay instead of talking to real players
+pub const REPLAY_ENV: &str = "NOW_PLAYING_REPLAY";
+// playback speed of the replay, 2.0 replays twice as fast
+pub const REPLAY_SPEED_ENV: &str = "NOW_PLAYING_REPLAY_SPEED";
+
+pub struct ReplayBackend {
+    events: Vec<(u64, BackendEvent)>,
+    speed: f64,
+}
+
+impl ReplayBackend {
+    pub fn from_capture(path: impl AsRef<Path>, speed: f64) -> Result<Self, MediaError> {
+        if !(speed.is_finite() && speed > 0.0) {
+            return Err(MediaError::backend_unavailable(format!(
+                "Invalid replay speed {speed}."
+            )));
+        }
+        let events = load_capture(path)?;
+        Ok(Self { events, speed })
+    }
+
+    pub fn from_env(path: String) -> Result<Self, MediaError> {
+        let speed = match std::env::var(REPLAY_SPEED_ENV) {
+            Ok(s) => match s.parse::<f64>() {
+                Ok(s) => s,
+                Err(_) => {
+                    return Err(MediaError::backend_unavailable(format!(
+                        "Invalid replay speed {s}."
+                    )));
+                }
+            },
+            Err(_) => 1.0,
+        };
+        ReplayBackend::from_capture(path, speed)
+    }
+
+    async fn replay(events: Vec<(u64, BackendEvent)>, speed: f64, tx: Sender<BackendEvent>) {
+        let start = Instant::now();
+        for (at_ms, mut event) in events {
+            let offset = Duration::from_secs_f64(at_ms as f64 / 1000.0 / speed);
+            sleep_until(start + offset).await;
+            shift_timestamps(&mut event, now_ms());
+            if tx.send(event).await.is_err() {
+                return;
+            }
+        }
+        println!("[replay] finished replaying capture");
+    }
+}
+
+impl MediaBackend for ReplayBackend {
+    fn control_session(
+        &self,
+        session_id: usize,
+        control: SessionControl,
+    ) -> Result<(), MediaError> {
+        // a capture only reproduces what happened, so there is nothing to control
+        Err(MediaError::control_rejected(format!(
+            "Failed to control session {session_id}: {:?}",
+            control
+        ))
+        .with_session(session_id))
+    }
+
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        let (tx, rx) = channel(64);
+        tauri::async_runtime::spawn(ReplayBackend::replay(self.events.clone(), self.speed, tx));
+        Ok(rx)
+    }
+}
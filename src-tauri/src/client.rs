@@ -0,0 +1,279 @@
+use std::{
+    collections::BTreeMap,
+    path::PathBuf,
+    sync::{Arc, Mutex as SyncMutex},
+    time::{Duration, Instant},
+};
+
+use tauri::{async_runtime::Receiver, AppHandle, Manager};
+
+use crate::{
+    artwork::ArtworkCache,
+    backend::{default_backend, BackendEvent, MediaBackend},
+    capture::recorder::Recorder,
+    emit_event,
+    error::MediaError,
+    history::{History, HistoryEntry, HistoryRange},
+    model::{ActiveSessionRemove, Artwork, CurrentSession, SessionControl},
+    position::{now_ms, SessionPosition},
+    services::Services,
+    store::SessionStore,
+    thumbnail::ThumbnailOptions,
+};
+
+// session_position is emitted at most this often per session
+const POSITION_TICK: Duration = Duration::from_millis(500);
+// a queued control that waited longer than this for an active session is no longer what was meant
+const QUEUED_CONTROL_TIMEOUT: Duration = Duration::from_secs(10);
+
+// every part is shared on its own, so the client is used from any thread without a lock around it
+#[derive(Clone)]
+pub struct MediaClient {
+    pub backend: Arc<dyn MediaBackend>,
+    pub store: Arc<SyncMutex<SessionStore>>,
+    pub artwork: Arc<ArtworkCache>,
+    pub history: Arc<SyncMutex<History>>,
+    // controls waiting for the first active session, with the time they expire at
+    queued_controls: Arc<SyncMutex<Vec<(Instant, SessionControl)>>>,
+}
+
+impl MediaClient {
+    pub fn new() -> Result<Self, MediaError> {
+        let backend = default_backend()?;
+        Ok(Self::with_backend(backend))
+    }
+
+    pub fn with_backend(backend: Box<dyn MediaBackend>) -> Self {
+        Self {
+            backend: Arc::from(backend),
+            store: Arc::new(SyncMutex::new(SessionStore::default())),
+            artwork: Arc::new(ArtworkCache::new(ThumbnailOptions::default())),
+            history: Arc::new(SyncMutex::new(History::default())),
+            queued_controls: Arc::new(SyncMutex::new(Vec::new())),
+        }
+    }
+
+    pub fn with_thumbnails(self, options: ThumbnailOptions) -> Self {
+        self.artwork.set_options(options);
+        self
+    }
+
+    // without a directory the art is only cached in memory
+    pub fn with_artwork_dir(mut self, dir: PathBuf) -> Self {
+        self.artwork = Arc::new(ArtworkCache::new(self.artwork.options()).with_dir(dir));
+        self
+    }
+
+    // without a file the history only lasts until the app exits
+    pub fn with_history_file(self, file: PathBuf) -> Self {
+        match History::open(file) {
+            Ok(h) => *self.history.lock().unwrap() = h,
+            Err(err) => println!("{}", err),
+        }
+        self
+    }
+
+    pub fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        self.backend.subscribe()
+    }
+
+    pub async fn init_event_handler(
+        handle: &AppHandle,
+        store: Arc<SyncMutex<SessionStore>>,
+        mut rx: Receiver<BackendEvent>,
+        mut recorder: Option<Recorder>,
+        artwork: Arc<ArtworkCache>,
+        history: Arc<SyncMutex<History>>,
+        services: Arc<SyncMutex<Services>>,
+    ) -> Result<(), MediaError> {
+        while let Some(mut evt) = rx.recv().await {
+            if let Some(r) = recorder.as_mut() {
+                if let Err(err) = r.record(&evt) {
+                    // keep the app running, the capture is only a debugging aid
+                    println!("{}\nrecording stopped", err);
+                    recorder = None;
+                }
+            }
+            // the raw art is recorded above, but only its id is sent on. decoding, resizing and the
+            // palette take a while, so they run on a blocking thread, and the event waits for them so
+            // that the events of a session stay in order
+            if let BackendEvent::SessionUpdate(update) = &mut evt {
+                if let Some(image) = update.image.take() {
+                    let artwork = Arc::clone(&artwork);
+                    let result =
+                        tauri::async_runtime::spawn_blocking(move || artwork.insert(&image)).await;
+                    match result {
+                        Ok(Ok(art)) => {
+                            update.art_id = Some(art.art_id.clone());
+                            update.palette = art.palette.clone();
+                        }
+                        Ok(Err(err)) => {
+                            println!("[{}/{}] {}", update.session_id, update.source, err)
+                        }
+                        Err(err) => println!("[{}/{}] {}", update.session_id, update.source, err),
+                    }
+                }
+            }
+            // the store is updated first, so that a frontend reacting to the event reads the new state
+            store.lock().unwrap().apply(&evt);
+            if let BackendEvent::ActiveSessionChange(Some(change)) = &evt {
+                let client = MediaClient::clone(&handle.state::<MediaClient>());
+                let controls = client.take_queued_controls();
+                if !controls.is_empty() {
+                    let session_id = change.session_id;
+                    // backends block while a control is sent
+                    tauri::async_runtime::spawn_blocking(move || {
+                        for control in controls {
+                            if let Err(err) = client.control_session(session_id, control) {
+                                println!("{}", err);
+                            }
+                        }
+                    });
+                }
+            }
+            let finished = history.lock().unwrap().apply(&evt, now_ms());
+            services
+                .lock()
+                .unwrap()
+                .apply(&evt, &finished, &store, &artwork);
+            match evt {
+                BackendEvent::SessionCreate(payload) => {
+                    emit_event("session_create", payload, handle);
+                }
+                BackendEvent::SessionUpdate(payload) => {
+                    emit_event("session_update", payload, handle);
+                }
+                BackendEvent::SessionRemove(payload) => {
+                    emit_event("session_remove", payload, handle);
+                }
+                BackendEvent::ActiveSessionChange(Some(payload)) => {
+                    emit_event("current_session_change", payload, handle);
+                }
+                BackendEvent::ActiveSessionChange(None) => {
+                    emit_event("current_session_remove", ActiveSessionRemove, handle);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn init_position_ticker(handle: &AppHandle, store: Arc<SyncMutex<SessionStore>>) {
+        let mut interval = tokio::time::interval(POSITION_TICK);
+        let mut last_sent = BTreeMap::<usize, SessionPosition>::new();
+        loop {
+            interval.tick().await;
+            let positions = store.lock().unwrap().positions(now_ms());
+            last_sent.retain(|id, _| positions.iter().any(|p| p.session_id == *id));
+            // paused sessions are sent once, playing ones on every tick
+            for position in positions {
+                if last_sent.get(&position.session_id) == Some(&position) {
+                    continue;
+                }
+                last_sent.insert(position.session_id, position.clone());
+                emit_event("session_position", position, handle);
+            }
+        }
+    }
+
+    pub fn get_position(&self, session_id: usize) -> Result<SessionPosition, MediaError> {
+        let store = self.store.lock().unwrap();
+        if store.get(session_id).is_none() {
+            return Err(
+                MediaError::not_found(format!("Session {session_id} no longer exists."))
+                    .with_session(session_id),
+            );
+        }
+        match store.position(session_id, now_ms()) {
+            Some(p) => Ok(p),
+            None => Err(MediaError::not_found(format!(
+                "Session {session_id} has not reported a timeline."
+            ))
+            .with_session(session_id)),
+        }
+    }
+
+    pub fn get_artwork(&self, art_id: &str) -> Result<Artwork, MediaError> {
+        match self.artwork.get(art_id) {
+            Some(a) => Ok(a.as_ref().clone()),
+            None => Err(MediaError::not_found(format!(
+                "Failed to find artwork {art_id}."
+            ))),
+        }
+    }
+
+    pub fn get_history(&self, range: HistoryRange, limit: Option<usize>) -> Vec<HistoryEntry> {
+        self.history.lock().unwrap().query(range, limit)
+    }
+
+    pub fn get_session(&self, session_id: usize) -> Result<CurrentSession, MediaError> {
+        match self.store.lock().unwrap().get(session_id) {
+            Some(s) => Ok(s.clone()),
+            None => Err(
+                MediaError::not_found(format!("Session {session_id} no longer exists."))
+                    .with_session(session_id),
+            ),
+        }
+    }
+
+    pub fn get_current_sessions(&self) -> Vec<CurrentSession> {
+        self.store.lock().unwrap().current_sessions()
+    }
+
+    // for controls asked for before any player was found, they go to the first active session
+    pub fn queue_control(&self, control: SessionControl) {
+        let expires_at = Instant::now() + QUEUED_CONTROL_TIMEOUT;
+        self.queued_controls
+            .lock()
+            .unwrap()
+            .push((expires_at, control));
+    }
+
+    fn take_queued_controls(&self) -> Vec<SessionControl> {
+        let queued = std::mem::take(&mut *self.queued_controls.lock().unwrap());
+        let now = Instant::now();
+        queued
+            .into_iter()
+            .filter_map(|(expires_at, control)| {
+                if expires_at < now {
+                    println!(
+                        "[client] dropped {:?}, no session was active in time",
+                        control
+                    );
+                    return None;
+                }
+                Some(control)
+            })
+            .collect()
+    }
+
+    pub fn control_session(
+        &self,
+        session_id: usize,
+        control: SessionControl,
+    ) -> Result<(), MediaError> {
+        let control = {
+            let store = self.store.lock().unwrap();
+            // ids the frontend still holds after a SessionRemove are rejected before reaching the
+            // backend
+            if store.get(session_id).is_none() {
+                return Err(MediaError::not_found(format!(
+                    "Session {session_id} no longer exists."
+                ))
+                .with_session(session_id));
+            }
+            // the position a backend reports is only true at its timestamp, so relative seeks start
+            // from the position the clock has moved it to since
+            match (control, store.position(session_id, now_ms())) {
+                (SessionControl::SeekBy(offset), Some(p)) => {
+                    let mut target = p.position.saturating_add(offset).max(p.start);
+                    if p.end > p.start {
+                        target = target.min(p.end);
+                    }
+                    SessionControl::SeekTo(target)
+                }
+                (control, _) => control,
+            }
+        };
+        self.backend.control_session(session_id, control)
+    }
+}
@@ -0,0 +1,457 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use discord_rich_presence::{
+    activity::{Activity, ActivityType, Assets, Timestamps},
+    DiscordIpc, DiscordIpcClient,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::MediaError,
+    model::{ticks_to_ms, CurrentSession, PlaybackStatus},
+    position::{now_ms, SessionPosition},
+    template::{Template, TemplateContext},
+};
+
+// discord drops updates sent faster than 5 per 20s
+const MIN_UPDATE_INTERVAL: Duration = Duration::from_secs(4);
+// how often a closed or missing discord is tried again while there is something to show
+const RECONNECT_DELAY: Duration = Duration::from_secs(15);
+// a position that moved by less than this is the same position, extrapolation jitters a little
+const TIMESTAMP_TOLERANCE_MS: i64 = 2_000;
+// discord rejects longer texts, and texts shorter than 2 characters
+const MAX_TEXT_LENGTH: usize = 128;
+const MIN_TEXT_LENGTH: usize = 2;
+
+// the [discord] table of the settings file, the presence is off without it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscordConfig {
+    // of the discord application the presence is shown for
+    pub client_id: String,
+    // parts of source names, e.g. "spotify". sources are shown when they contain one of these, or always when this is empty
+    #[serde(default)]
+    pub allow: Vec<String>,
+    // sources containing one of these are never shown, even when allowed
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default = "default_details")]
+    pub details: String,
+    #[serde(default = "default_state")]
+    pub state: String,
+    #[serde(default = "default_large_text")]
+    pub large_text: String,
+    // an art asset uploaded to the discord application
+    #[serde(default = "default_large_image")]
+    pub large_image: String,
+}
+
+fn default_details() -> String {
+    "{title}".to_string()
+}
+
+fn default_state() -> String {
+    "{if artist}by {artist}{else}{source}{end}".to_string()
+}
+
+fn default_large_text() -> String {
+    "{album | default(\"Now Playing\")}".to_string()
+}
+
+fn default_large_image() -> String {
+    "now_playing".to_string()
+}
+
+impl DiscordConfig {
+    // the templates are parsed again when the presence starts, this reports problems before that
+    pub fn validate(&self) -> Result<(), MediaError> {
+        if self.client_id.is_empty() {
+            return Err(MediaError::conversion_failed(
+                "The discord client id is empty.",
+            ));
+        }
+        for template in [&self.details, &self.state, &self.large_text] {
+            Template::parse(template)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_allowed(&self, source: &str) -> bool {
+        let source = source.to_lowercase();
+        let matches = |list: &[String]| list.iter().any(|s| source.contains(&s.to_lowercase()));
+        (self.allow.is_empty() || matches(&self.allow)) && !matches(&self.deny)
+    }
+}
+
+// what is shown for a session, compared before sending so that unchanged updates are skipped
+#[derive(Debug, Clone, PartialEq)]
+struct Presence {
+    details: String,
+    state: String,
+    large_text: String,
+    small_image: String,
+    small_text: String,
+    // unix ms the track started and will end at, only while playing
+    timestamps: Option<(i64, i64)>,
+}
+
+impl Presence {
+    fn is_same(&self, other: &Presence) -> bool {
+        let timestamps_match = match (self.timestamps, other.timestamps) {
+            (Some((a, _)), Some((b, _))) => (a - b).abs() < TIMESTAMP_TOLERANCE_MS,
+            (None, None) => true,
+            _ => false,
+        };
+        timestamps_match
+            && self.details == other.details
+            && self.state == other.state
+            && self.large_text == other.large_text
+            && self.small_image == other.small_image
+    }
+}
+
+// the asset key for a source: "Spotify.exe" is "spotify", "org.mpris.MediaPlayer2.vlc" is "vlc"
+// and "SpotifyAB.SpotifyMusic_zpdnekdrzrea0!Spotify" is "spotify"
+pub fn source_asset_key(source: &str) -> String {
+    let source = source.trim();
+    let source = source.strip_suffix(".exe").unwrap_or(source);
+    let name = source
+        .rsplit(['!', '.', '/', '\\'])
+        .next()
+        .unwrap_or(source);
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_' || *c == '-')
+        .collect::<String>()
+        .to_lowercase()
+}
+
+// discord rejects the whole activity when a text is out of bounds
+fn fit_text(text: String) -> String {
+    let mut text = text
+        .trim()
+        .chars()
+        .take(MAX_TEXT_LENGTH)
+        .collect::<String>();
+    while text.chars().count() < MIN_TEXT_LENGTH {
+        // invisible padding, a one letter title is still a title
+        text.push('\u{2800}');
+    }
+    text
+}
+
+// follows the active session and hands presences to a worker thread, since discord may take a
+// while to answer or not be running at all
+pub struct DiscordPresence {
+    config: DiscordConfig,
+    details: Template,
+    state: Template,
+    large_text: Template,
+    updates: Sender<Option<Presence>>,
+    // None before the first update, Some(None) while the presence is cleared
+    shown: Option<Option<Presence>>,
+}
+
+impl DiscordPresence {
+    pub fn start(config: DiscordConfig) -> Result<Self, MediaError> {
+        DiscordPresence::start_with_interval(config, MIN_UPDATE_INTERVAL)
+    }
+
+    // the tests send faster than discord accepts
+    fn start_with_interval(
+        config: DiscordConfig,
+        min_interval: Duration,
+    ) -> Result<Self, MediaError> {
+        let details = Template::parse(&config.details)?;
+        let state = Template::parse(&config.state)?;
+        let large_text = Template::parse(&config.large_text)?;
+        let (updates, rx) = mpsc::channel();
+        let client_id = config.client_id.clone();
+        let large_image = config.large_image.clone();
+        thread::spawn(move || run(client_id, large_image, min_interval, rx));
+        Ok(Self {
+            config,
+            details,
+            state,
+            large_text,
+            updates,
+            shown: None,
+        })
+    }
+
+    // called after the store has applied an event, clears the presence without an active session
+    pub fn apply(&mut self, active: Option<&CurrentSession>, position: Option<&SessionPosition>) {
+        let presence = active
+            .filter(|s| self.config.is_allowed(&s.source))
+            .and_then(|s| self.presence(s, position));
+        let unchanged = match (self.shown.as_ref(), presence.as_ref()) {
+            (Some(Some(shown)), Some(presence)) => shown.is_same(presence),
+            (Some(None), None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return;
+        }
+        if self.updates.send(presence.clone()).is_err() {
+            println!("[discord] worker stopped");
+        }
+        self.shown = Some(presence);
+    }
+
+    fn presence(
+        &self,
+        session: &CurrentSession,
+        position: Option<&SessionPosition>,
+    ) -> Option<Presence> {
+        let model = &session.session;
+        model.media.as_ref().filter(|m| !m.title.is_empty())?;
+        let ctx = TemplateContext::new(model, position);
+        let playing = model
+            .playback
+            .as_ref()
+            .is_some_and(|p| p.status == PlaybackStatus::Playing);
+        // elapsed and remaining are shown as a bar between these two
+        let timestamps = model
+            .timeline
+            .as_ref()
+            .filter(|t| playing && t.end > t.start)
+            .map(|t| {
+                let position = position.map_or(t.position, |p| p.position);
+                let start = now_ms() - ticks_to_ms(position - t.start);
+                (start, start + ticks_to_ms(t.end - t.start))
+            });
+        Some(Presence {
+            details: fit_text(self.details.render(&ctx)),
+            state: fit_text(self.state.render(&ctx)),
+            large_text: fit_text(self.large_text.render(&ctx)),
+            small_image: source_asset_key(&session.source),
+            small_text: fit_text(session.source.clone()),
+            timestamps,
+        })
+    }
+}
+
+fn run(
+    client_id: String,
+    large_image: String,
+    min_interval: Duration,
+    updates: Receiver<Option<Presence>>,
+) {
+    let mut client: Option<DiscordIpcClient> = None;
+    // the latest presence that has not been sent yet
+    let mut pending: Option<Option<Presence>> = None;
+    let mut next_send = Instant::now();
+    let mut failing = false;
+    loop {
+        let update = match pending {
+            Some(_) => updates.recv_timeout(next_send.saturating_duration_since(Instant::now())),
+            None => updates.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match update {
+            // only the latest update matters, so earlier ones are replaced before sending
+            Ok(presence) => {
+                pending = Some(presence);
+                continue;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                if let Some(mut c) = client {
+                    let _ = c.clear_activity();
+                    let _ = c.close();
+                }
+                return;
+            }
+        }
+        let Some(presence) = pending.take() else {
+            continue;
+        };
+        match send(&mut client, &client_id, &large_image, presence.as_ref()) {
+            Ok(()) => {
+                failing = false;
+                next_send = Instant::now() + min_interval;
+            }
+            Err(err) => {
+                // discord not running is the usual case, so it is only reported once
+                if !failing {
+                    println!("[discord] {}", err);
+                    failing = true;
+                }
+                client = None;
+                // nothing to clear on a discord that is not there
+                pending = presence.map(Some);
+                next_send = Instant::now() + RECONNECT_DELAY;
+            }
+        }
+    }
+}
+
+fn send(
+    client: &mut Option<DiscordIpcClient>,
+    client_id: &str,
+    large_image: &str,
+    presence: Option<&Presence>,
+) -> Result<(), MediaError> {
+    let client = match client {
+        Some(c) => c,
+        None if presence.is_none() => return Ok(()),
+        None => {
+            let mut c = DiscordIpcClient::new(client_id);
+            if let Err(err) = c.connect() {
+                return Err(
+                    MediaError::service_failed("Failed to connect to Discord.").with_source(err)
+                );
+            }
+            client.insert(c)
+        }
+    };
+    let result = match presence {
+        Some(p) => {
+            let mut assets = Assets::new()
+                .large_text(&p.large_text)
+                .small_image(&p.small_image)
+                .small_text(&p.small_text);
+            if !large_image.is_empty() {
+                assets = assets.large_image(large_image);
+            }
+            let mut activity = Activity::new()
+                .activity_type(ActivityType::Listening)
+                .details(&p.details)
+                .state(&p.state)
+                .assets(assets);
+            if let Some((start, end)) = p.timestamps {
+                activity = activity.timestamps(Timestamps::new().start(start).end(end));
+            }
+            client.set_activity(activity)
+        }
+        None => client.clear_activity(),
+    };
+    // every command is answered, reading the answer keeps the socket from filling up
+    let response = match result.and_then(|()| client.recv()) {
+        Ok((_, r)) => r,
+        Err(err) => {
+            return Err(
+                MediaError::service_failed("Failed to update the Discord presence.")
+                    .with_source(err),
+            );
+        }
+    };
+    if response["evt"] == "ERROR" {
+        // a rejected activity is not a connection problem, so the client is kept
+        println!(
+            "[discord] presence rejected: {}",
+            response["data"]["message"].as_str().unwrap_or_default()
+        );
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        os::unix::net::UnixListener,
+        path::Path,
+    };
+
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::model::{test_session, TimelineModel, TICKS_PER_MILLISECOND};
+
+    // answers every frame like discord does and hands the payloads to the test
+    fn fake_discord(dir: &Path) -> Receiver<(u32, Value)> {
+        let listener = UnixListener::bind(dir.join("discord-ipc-0")).unwrap();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let Ok((mut socket, _)) = listener.accept() else {
+                return;
+            };
+            let mut header = [0; 8];
+            while socket.read_exact(&mut header).is_ok() {
+                let opcode = u32::from_le_bytes(header[..4].try_into().unwrap());
+                let length = u32::from_le_bytes(header[4..].try_into().unwrap());
+                let mut data = vec![0; length as usize];
+                socket.read_exact(&mut data).unwrap();
+                let payload = serde_json::from_slice::<Value>(&data).unwrap();
+                let reply = json!({ "cmd": payload["cmd"], "evt": null }).to_string();
+                let mut frame = 1u32.to_le_bytes().to_vec();
+                frame.extend((reply.len() as u32).to_le_bytes());
+                frame.extend(reply.as_bytes());
+                socket.write_all(&frame).unwrap();
+                if tx.send((opcode, payload)).is_err() {
+                    return;
+                }
+            }
+        });
+        rx
+    }
+
+    fn session(source: &str, status: PlaybackStatus) -> CurrentSession {
+        let mut model = test_session("Song", "Band");
+        model.playback.as_mut().unwrap().status = status;
+        model.timeline = Some(TimelineModel {
+            start: 0,
+            end: 200_000 * TICKS_PER_MILLISECOND,
+            position: 50_000 * TICKS_PER_MILLISECOND,
+            last_updated_at_ms: now_ms(),
+        });
+        CurrentSession {
+            session_id: 0,
+            source: source.to_string(),
+            session: model,
+            art_id: None,
+            palette: None,
+        }
+    }
+
+    fn next_activity(frames: &Receiver<(u32, Value)>) -> Value {
+        let (opcode, payload) = frames.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((opcode, &payload["cmd"]), (1, &json!("SET_ACTIVITY")));
+        payload["args"]["activity"].clone()
+    }
+
+    #[test]
+    fn shows_allowed_sessions_and_clears_the_rest() {
+        let dir = std::env::temp_dir().join(format!("now-playing-discord-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // the first place the client looks for the socket of a running discord
+        std::env::set_var("XDG_RUNTIME_DIR", &dir);
+        let frames = fake_discord(&dir);
+
+        let mut config = toml::from_str::<DiscordConfig>("clientId = \"1234\"").unwrap();
+        config.allow = vec!["spotify".to_string(), "firefox".to_string()];
+        config.deny = vec!["firefox".to_string()];
+        let mut presence =
+            DiscordPresence::start_with_interval(config, Duration::from_millis(0)).unwrap();
+
+        presence.apply(Some(&session("Spotify.exe", PlaybackStatus::Playing)), None);
+        let (opcode, handshake) = frames.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!((opcode, handshake["client_id"].as_str()), (0, Some("1234")));
+        let activity = next_activity(&frames);
+        assert_eq!(activity["details"], "Song");
+        assert_eq!(activity["state"], "by Band");
+        assert_eq!(activity["assets"]["small_image"], "spotify");
+        let timestamps = &activity["timestamps"];
+        let length = timestamps["end"].as_i64().unwrap() - timestamps["start"].as_i64().unwrap();
+        assert_eq!(length, 200_000);
+
+        // a paused track is still shown, without a time bar
+        presence.apply(Some(&session("Spotify.exe", PlaybackStatus::Paused)), None);
+        let activity = next_activity(&frames);
+        assert_eq!(activity["details"], "Song");
+        assert!(activity.get("timestamps").is_none());
+
+        presence.apply(Some(&session("firefox.exe", PlaybackStatus::Playing)), None);
+        assert!(next_activity(&frames).is_null());
+
+        presence.apply(Some(&session("Spotify.exe", PlaybackStatus::Playing)), None);
+        assert!(next_activity(&frames).is_object());
+        presence.apply(None, None);
+        assert!(next_activity(&frames).is_null());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
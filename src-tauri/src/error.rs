@@ -0,0 +1,103 @@
+use std::fmt;
+
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
+
+type SourceError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    NotFound,
+    ControlRejected,
+    BackendUnavailable,
+    ConversionFailed,
+    ThumbnailFailed,
+    StorageFailed,
+    ServiceFailed,
+}
+
+#[derive(Debug)]
+pub struct MediaError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub session_id: Option<usize>,
+    pub source: Option<SourceError>,
+}
+
+impl MediaError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            session_id: None,
+            source: None,
+        }
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::NotFound, message)
+    }
+
+    pub fn control_rejected(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::ControlRejected, message)
+    }
+
+    pub fn backend_unavailable(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::BackendUnavailable, message)
+    }
+
+    pub fn conversion_failed(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::ConversionFailed, message)
+    }
+
+    pub fn thumbnail_failed(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::ThumbnailFailed, message)
+    }
+
+    pub fn storage_failed(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::StorageFailed, message)
+    }
+
+    pub fn service_failed(message: impl Into<String>) -> Self {
+        Self::new(ErrorKind::ServiceFailed, message)
+    }
+
+    pub fn with_session(mut self, session_id: usize) -> Self {
+        self.session_id = Some(session_id);
+        self
+    }
+
+    pub fn with_source(mut self, source: impl Into<SourceError>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+}
+
+impl fmt::Display for MediaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+        if let Some(source) = &self.source {
+            write!(f, "\n{}", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MediaError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|s| s.as_ref() as &(dyn std::error::Error + 'static))
+    }
+}
+
+// the source error is flattened into a string, since the frontend only displays it
+impl Serialize for MediaError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("MediaError", 4)?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("message", &self.message)?;
+        state.serialize_field("sessionId", &self.session_id)?;
+        state.serialize_field("cause", &self.source.as_ref().map(|s| s.to_string()))?;
+        state.end()
+    }
+}
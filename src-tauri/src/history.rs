@@ -0,0 +1,365 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::BackendEvent,
+    error::MediaError,
+    model::{ticks_to_ms, MediaModel, PlaybackStatus, SessionModel},
+};
+
+// one HistoryEntry per line, only ever appended to
+pub const HISTORY_FILE_NAME: &str = "history.jsonl";
+
+// a play counts once it ran this long, or half of a shorter track
+const MIN_PLAYED_MS: i64 = 30_000;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub source: String,
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    // length of the track as reported by the timeline, if there was one
+    pub duration_ms: Option<i64>,
+    // unix time of the first and last moment the track was playing
+    pub started_at_ms: i64,
+    pub ended_at_ms: i64,
+    // time actually spent playing, without pauses
+    pub played_ms: i64,
+}
+
+// both ends are inclusive and compared against started_at_ms
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRange {
+    pub from_ms: Option<i64>,
+    pub to_ms: Option<i64>,
+}
+
+impl HistoryRange {
+    fn contains(&self, at_ms: i64) -> bool {
+        !matches!(self.from_ms, Some(from) if at_ms < from)
+            && !matches!(self.to_ms, Some(to) if at_ms > to)
+    }
+}
+
+// the track a session is on right now
+struct Play {
+    source: String,
+    title: String,
+    artist: String,
+    album: Option<String>,
+    duration_ms: Option<i64>,
+    started_at_ms: Option<i64>,
+    ended_at_ms: i64,
+    played_ms: i64,
+    playing_since_ms: Option<i64>,
+}
+
+impl Play {
+    fn new(source: &str, media: &MediaModel) -> Self {
+        Self {
+            source: source.to_string(),
+            title: media.title.clone(),
+            artist: media.artist.clone(),
+            album: media
+                .album
+                .as_ref()
+                .map(|a| a.title.clone())
+                .filter(|t| !t.is_empty()),
+            duration_ms: None,
+            started_at_ms: None,
+            ended_at_ms: 0,
+            played_ms: 0,
+            playing_since_ms: None,
+        }
+    }
+
+    fn is_track(&self, media: &MediaModel) -> bool {
+        self.title == media.title
+            && self.artist == media.artist
+            && self.album.as_deref().unwrap_or_default()
+                == media.album.as_ref().map_or("", |a| a.title.as_str())
+    }
+
+    fn update(&mut self, model: &SessionModel, now_ms: i64) {
+        if let Some(timeline) = model.timeline.as_ref() {
+            if timeline.end > timeline.start {
+                self.duration_ms = Some(ticks_to_ms(timeline.end - timeline.start));
+            }
+        }
+        let playing = model
+            .playback
+            .as_ref()
+            .is_some_and(|p| p.status == PlaybackStatus::Playing);
+        match (self.playing_since_ms, playing) {
+            (None, true) => {
+                self.started_at_ms.get_or_insert(now_ms);
+                self.playing_since_ms = Some(now_ms);
+            }
+            (Some(_), false) => self.pause(now_ms),
+            _ => {}
+        }
+    }
+
+    fn pause(&mut self, now_ms: i64) {
+        if let Some(since) = self.playing_since_ms.take() {
+            self.played_ms += (now_ms - since).max(0);
+            self.ended_at_ms = now_ms;
+        }
+    }
+
+    fn finish(mut self, now_ms: i64) -> Option<HistoryEntry> {
+        self.pause(now_ms);
+        let required = match self.duration_ms {
+            Some(d) if d > 0 => MIN_PLAYED_MS.min(d / 2),
+            _ => MIN_PLAYED_MS,
+        };
+        if self.played_ms < required {
+            return None;
+        }
+        Some(HistoryEntry {
+            source: self.source,
+            title: self.title,
+            artist: self.artist,
+            album: self.album,
+            duration_ms: self.duration_ms,
+            started_at_ms: self.started_at_ms?,
+            ended_at_ms: self.ended_at_ms,
+            played_ms: self.played_ms,
+        })
+    }
+}
+
+// turns the update stream into finished plays and keeps every play recorded so far
+#[derive(Default)]
+pub struct History {
+    plays: BTreeMap<usize, Play>,
+    entries: Vec<HistoryEntry>,
+    file: Option<PathBuf>,
+}
+
+impl History {
+    pub fn open(file: impl Into<PathBuf>) -> Result<Self, MediaError> {
+        let file = file.into();
+        if let Some(dir) = file.parent() {
+            if let Err(err) = fs::create_dir_all(dir) {
+                return Err(
+                    MediaError::storage_failed("Failed to create history directory.")
+                        .with_source(err),
+                );
+            }
+        }
+        let content = match fs::read_to_string(&file) {
+            Ok(c) => c,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => {
+                return Err(MediaError::storage_failed("Failed to read history.").with_source(err));
+            }
+        };
+        let mut entries = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            // a crash can cut the last line short, which should not lose the rest
+            match serde_json::from_str::<HistoryEntry>(line) {
+                Ok(e) => entries.push(e),
+                Err(err) => println!("[history] skipping line {}: {}", i + 1, err),
+            }
+        }
+        // a cut short line is terminated, so that the next entry starts on a line of its own
+        if !content.is_empty() && !content.ends_with('\n') {
+            if let Err(err) = append_line(&file, "") {
+                return Err(MediaError::storage_failed("Failed to write history.").with_source(err));
+            }
+        }
+        Ok(Self {
+            plays: BTreeMap::new(),
+            entries,
+            file: Some(file),
+        })
+    }
+
+    // returns the plays that finished with this event, already written to the history
+    pub fn apply(&mut self, event: &BackendEvent, now_ms: i64) -> Vec<HistoryEntry> {
+        let mut finished = Vec::new();
+        match event {
+            BackendEvent::SessionUpdate(update) => {
+                let model = &update.session_model;
+                // players drop the media for a moment between tracks or while buffering, so only
+                // another track ends the play
+                let media = model.media.as_ref().filter(|m| !m.title.is_empty());
+                if let Some(media) = media {
+                    let same_track = self
+                        .plays
+                        .get(&update.session_id)
+                        .is_some_and(|play| play.is_track(media));
+                    if !same_track {
+                        if let Some(play) = self.plays.remove(&update.session_id) {
+                            finished.extend(play.finish(now_ms));
+                        }
+                        self.plays
+                            .insert(update.session_id, Play::new(&update.source, media));
+                    }
+                }
+                if let Some(play) = self.plays.get_mut(&update.session_id) {
+                    play.update(model, now_ms);
+                }
+            }
+            BackendEvent::SessionRemove(remove) => {
+                if let Some(play) = self.plays.remove(&remove.session_id) {
+                    finished.extend(play.finish(now_ms));
+                }
+            }
+            BackendEvent::SessionCreate(_) | BackendEvent::ActiveSessionChange(_) => {}
+        }
+        for entry in finished.iter() {
+            if let Err(err) = self.append(entry) {
+                println!("{}", err);
+            }
+            self.entries.push(entry.clone());
+        }
+        finished
+    }
+
+    fn append(&self, entry: &HistoryEntry) -> Result<(), MediaError> {
+        let Some(file) = self.file.as_ref() else {
+            return Ok(());
+        };
+        let line = match serde_json::to_string(entry) {
+            Ok(l) => l,
+            Err(err) => {
+                return Err(
+                    MediaError::storage_failed("Failed to serialize history entry.")
+                        .with_source(err),
+                );
+            }
+        };
+        match append_line(file, &line) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                Err(MediaError::storage_failed("Failed to write history.").with_source(err))
+            }
+        }
+    }
+
+    // newest first
+    pub fn query(&self, range: HistoryRange, limit: Option<usize>) -> Vec<HistoryEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .filter(|e| range.contains(e.started_at_ms))
+            .take(limit.unwrap_or(usize::MAX))
+            .cloned()
+            .collect()
+    }
+}
+
+// the whole line goes out in a single write, so entries never interleave
+fn append_line(file: &Path, line: &str) -> std::io::Result<()> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file)?
+        .write_all(format!("{line}\n").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{
+        test_session, SessionRemove, SessionUpdate, TimelineModel, TICKS_PER_MILLISECOND,
+    };
+
+    const MINUTE_MS: i64 = 60_000;
+
+    fn update(title: &str, status: PlaybackStatus, duration_ms: i64) -> BackendEvent {
+        let mut model = test_session(title, "Band");
+        model.playback.as_mut().unwrap().status = status;
+        model.timeline = Some(TimelineModel {
+            start: 0,
+            end: duration_ms * TICKS_PER_MILLISECOND,
+            position: 0,
+            last_updated_at_ms: 0,
+        });
+        BackendEvent::SessionUpdate(SessionUpdate {
+            session_id: 1,
+            source: model.source.clone(),
+            session_model: model,
+            image: None,
+            art_id: None,
+            palette: None,
+        })
+    }
+
+    fn remove() -> BackendEvent {
+        BackendEvent::SessionRemove(SessionRemove { session_id: 1 })
+    }
+
+    #[test]
+    fn pauses_do_not_count_as_played() {
+        let mut history = History::default();
+        assert!(history
+            .apply(&update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS), 0)
+            .is_empty());
+        history.apply(
+            &update("Song", PlaybackStatus::Paused, 4 * MINUTE_MS),
+            20_000,
+        );
+        history.apply(
+            &update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS),
+            MINUTE_MS,
+        );
+        let finished = history.apply(
+            &update("Next", PlaybackStatus::Playing, 4 * MINUTE_MS),
+            MINUTE_MS + 15_000,
+        );
+
+        assert_eq!(finished.len(), 1);
+        let entry = &finished[0];
+        assert_eq!(entry.title, "Song");
+        assert_eq!(entry.duration_ms, Some(4 * MINUTE_MS));
+        assert_eq!(entry.started_at_ms, 0);
+        assert_eq!(entry.ended_at_ms, MINUTE_MS + 15_000);
+        assert_eq!(entry.played_ms, 35_000);
+        assert_eq!(history.query(HistoryRange::default(), None), finished);
+    }
+
+    #[test]
+    fn short_plays_are_dropped() {
+        let mut history = History::default();
+        history.apply(&update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS), 0);
+        assert!(history.apply(&remove(), 29_000).is_empty());
+
+        // half of a short track is enough
+        history.apply(&update("Jingle", PlaybackStatus::Playing, 20_000), 0);
+        assert_eq!(history.apply(&remove(), 10_000).len(), 1);
+    }
+
+    #[test]
+    fn missing_media_does_not_end_the_play() {
+        let mut history = History::default();
+        history.apply(&update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS), 0);
+        let mut gap = update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS);
+        if let BackendEvent::SessionUpdate(update) = &mut gap {
+            update.session_model.media = None;
+        }
+        assert!(history.apply(&gap, 10_000).is_empty());
+        history.apply(
+            &update("Song", PlaybackStatus::Playing, 4 * MINUTE_MS),
+            20_000,
+        );
+        let finished = history.apply(&remove(), 40_000);
+
+        assert_eq!(finished.len(), 1);
+        assert_eq!(finished[0].played_ms, 40_000);
+    }
+}
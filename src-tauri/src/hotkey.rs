@@ -0,0 +1,312 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex as SyncMutex},
+    thread,
+};
+
+use arboard::Clipboard;
+use global_hotkey::{hotkey::HotKey, GlobalHotKeyEvent, GlobalHotKeyManager, HotKeyState};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    client::MediaClient,
+    error::MediaError,
+    instance,
+    model::{CurrentSession, SessionControl},
+    position::now_ms,
+    store::SessionStore,
+    template::{Template, TemplateContext},
+};
+
+// what a hotkey does, either a control of the target session or something of the app itself,
+// written like SessionControl: "SkipNext", { SeekBy = 100000000 } or "ToggleWindow"
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum HotkeyAction {
+    App(AppAction),
+    Control(SessionControl),
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AppAction {
+    ToggleWindow,
+    ToggleMini,
+    CopyNowPlaying,
+}
+
+// the [hotkeys] table of the settings file
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyConfig {
+    // shortcuts like "Ctrl+Alt+Space" or "CmdOrCtrl+Shift+ArrowRight" and their actions
+    #[serde(default)]
+    pub bindings: BTreeMap<String, HotkeyAction>,
+    // controls go to the session whose source contains this while there is one, e.g. "spotify"
+    #[serde(default)]
+    pub pin: Option<String>,
+    // the text CopyNowPlaying puts on the clipboard
+    #[serde(default = "default_copy_format")]
+    pub copy_format: String,
+}
+
+fn default_copy_format() -> String {
+    "{if artist}{artist} - {end}{title}".to_string()
+}
+
+impl Default for HotkeyConfig {
+    fn default() -> Self {
+        Self {
+            bindings: BTreeMap::new(),
+            pin: None,
+            copy_format: default_copy_format(),
+        }
+    }
+}
+
+// a configured shortcut and why it is not registered, if it is not
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct HotkeyBinding {
+    pub shortcut: String,
+    pub action: HotkeyAction,
+    pub error: Option<String>,
+}
+
+// what the listener thread needs to act on a hotkey id
+struct Targets {
+    actions: HashMap<u32, HotkeyAction>,
+    pin: Option<String>,
+    copy_format: Template,
+}
+
+// the windows and macos managers only work on the thread with the event loop, so the one manager
+// lives there and is only reached through run_on_main_thread
+thread_local! {
+    static MANAGER: RefCell<Option<GlobalHotKeyManager>> = const { RefCell::new(None) };
+}
+
+// what the manager has registered, only changed on the main thread
+#[derive(Default)]
+struct Registered {
+    hotkeys: Vec<HotKey>,
+    bindings: Vec<HotkeyBinding>,
+}
+
+pub struct Hotkeys {
+    handle: AppHandle,
+    registered: Arc<SyncMutex<Registered>>,
+    targets: Arc<SyncMutex<Targets>>,
+}
+
+impl Hotkeys {
+    // must be called on the main thread, like tauri's setup
+    pub fn start(handle: AppHandle, config: HotkeyConfig) -> Result<Self, MediaError> {
+        let manager = match GlobalHotKeyManager::new() {
+            Ok(m) => m,
+            Err(err) => {
+                return Err(
+                    MediaError::service_failed("Failed to start global hotkeys.").with_source(err),
+                );
+            }
+        };
+        MANAGER.with(|m| *m.borrow_mut() = Some(manager));
+        let targets = Arc::new(SyncMutex::new(Targets {
+            actions: HashMap::new(),
+            pin: None,
+            copy_format: Template::parse(&default_copy_format())?,
+        }));
+        let listener_handle = handle.clone();
+        let listener_targets = Arc::clone(&targets);
+        thread::spawn(move || listen(listener_handle, listener_targets));
+        let hotkeys = Self {
+            handle,
+            registered: Arc::new(SyncMutex::new(Registered::default())),
+            targets,
+        };
+        hotkeys.register(config)?;
+        Ok(hotkeys)
+    }
+
+    // replaces every registered hotkey once the main thread gets to it. a shortcut that is
+    // invalid or taken by another app is reported in its binding and the others still register
+    pub fn register(&self, config: HotkeyConfig) -> Result<(), MediaError> {
+        let copy_format = Template::parse(&config.copy_format)?;
+        let registered = Arc::clone(&self.registered);
+        let targets = Arc::clone(&self.targets);
+        let result = self.handle.run_on_main_thread(move || {
+            MANAGER.with(|manager| {
+                let manager = manager.borrow();
+                let Some(manager) = manager.as_ref() else {
+                    return;
+                };
+                let mut registered = registered.lock().unwrap();
+                let actions = register_all(manager, &mut registered, config.bindings);
+                *targets.lock().unwrap() = Targets {
+                    actions,
+                    pin: config.pin.filter(|p| !p.is_empty()),
+                    copy_format,
+                };
+            });
+        });
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => Err(
+                MediaError::service_failed("Failed to register global hotkeys.").with_source(err),
+            ),
+        }
+    }
+
+    pub fn bindings(&self) -> Vec<HotkeyBinding> {
+        self.registered.lock().unwrap().bindings.clone()
+    }
+}
+
+impl Drop for Hotkeys {
+    fn drop(&mut self) {
+        let hotkeys = std::mem::take(&mut self.registered.lock().unwrap().hotkeys);
+        let _ = self.handle.run_on_main_thread(move || {
+            MANAGER.with(|manager| {
+                if let Some(manager) = manager.borrow().as_ref() {
+                    let _ = manager.unregister_all(&hotkeys);
+                }
+            });
+        });
+    }
+}
+
+// runs on the main thread, returns the action behind the id of every hotkey that registered
+fn register_all(
+    manager: &GlobalHotKeyManager,
+    registered: &mut Registered,
+    bindings: BTreeMap<String, HotkeyAction>,
+) -> HashMap<u32, HotkeyAction> {
+    if let Err(err) = manager.unregister_all(&registered.hotkeys) {
+        println!("[hotkeys] {}", err);
+    }
+    registered.hotkeys.clear();
+    registered.bindings.clear();
+    let mut actions = HashMap::new();
+    for (shortcut, action) in bindings {
+        let result = shortcut
+            .parse::<HotKey>()
+            .map_err(|err| err.to_string())
+            .and_then(|hotkey| match manager.register(hotkey) {
+                Ok(()) => Ok(hotkey),
+                Err(
+                    global_hotkey::Error::AlreadyRegistered(_)
+                    | global_hotkey::Error::FailedToRegister(_),
+                ) => Err(format!("{shortcut} is already taken.")),
+                Err(err) => Err(err.to_string()),
+            });
+        let error = match result {
+            Ok(hotkey) => {
+                registered.hotkeys.push(hotkey);
+                actions.insert(hotkey.id(), action.clone());
+                None
+            }
+            Err(err) => {
+                println!("[hotkeys] {}", err);
+                Some(err)
+            }
+        };
+        registered.bindings.push(HotkeyBinding {
+            shortcut,
+            action,
+            error,
+        });
+    }
+    actions
+}
+
+fn listen(handle: AppHandle, targets: Arc<SyncMutex<Targets>>) {
+    // kept for the lifetime of the thread, some platforms lose the copied text with the clipboard
+    let mut clipboard: Option<Clipboard> = None;
+    while let Ok(event) = GlobalHotKeyEvent::receiver().recv() {
+        if event.state != HotKeyState::Pressed {
+            continue;
+        }
+        let (action, pin, copy_format) = {
+            let targets = targets.lock().unwrap();
+            let Some(action) = targets.actions.get(&event.id) else {
+                continue;
+            };
+            (
+                action.clone(),
+                targets.pin.clone(),
+                targets.copy_format.clone(),
+            )
+        };
+        let result = match action {
+            HotkeyAction::Control(control) => control_target(&handle, pin.as_deref(), control),
+            HotkeyAction::App(AppAction::ToggleWindow) => instance::toggle_window(&handle),
+            HotkeyAction::App(AppAction::ToggleMini) => instance::toggle_mini(&handle),
+            HotkeyAction::App(AppAction::CopyNowPlaying) => {
+                copy_now_playing(&handle, pin.as_deref(), &copy_format, &mut clipboard)
+            }
+        };
+        if let Err(err) = result {
+            println!("[hotkeys] {}", err);
+        }
+    }
+}
+
+// the pinned session while it is around, the active one otherwise
+fn target(store: &SessionStore, pin: Option<&str>) -> Option<CurrentSession> {
+    let pinned = pin.and_then(|pin| {
+        let pin = pin.to_lowercase();
+        store
+            .current_sessions()
+            .into_iter()
+            .find(|s| s.source.to_lowercase().contains(&pin))
+    });
+    pinned.or_else(|| store.active().cloned())
+}
+
+fn control_target(
+    handle: &AppHandle,
+    pin: Option<&str>,
+    control: SessionControl,
+) -> Result<(), MediaError> {
+    let client = handle.state::<MediaClient>();
+    let target = target(&client.store.lock().unwrap(), pin);
+    let Some(session) = target else {
+        return Err(MediaError::not_found("No session is active."));
+    };
+    client.control_session(session.session_id, control)
+}
+
+fn copy_now_playing(
+    handle: &AppHandle,
+    pin: Option<&str>,
+    copy_format: &Template,
+    clipboard: &mut Option<Clipboard>,
+) -> Result<(), MediaError> {
+    let text = {
+        let client = handle.state::<MediaClient>();
+        let store = client.store.lock().unwrap();
+        let Some(session) = target(&store, pin) else {
+            return Err(MediaError::not_found("No session is active."));
+        };
+        let position = store.position(session.session_id, now_ms());
+        copy_format.render(&TemplateContext::new(&session.session, position.as_ref()))
+    };
+    let clipboard = match clipboard {
+        Some(c) => c,
+        None => match Clipboard::new() {
+            Ok(c) => clipboard.insert(c),
+            Err(err) => {
+                return Err(
+                    MediaError::service_failed("Failed to open the clipboard.").with_source(err)
+                );
+            }
+        },
+    };
+    match clipboard.set_text(text) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            Err(MediaError::service_failed("Failed to copy to the clipboard.").with_source(err))
+        }
+    }
+}
@@ -0,0 +1,163 @@
+use tauri::{AppHandle, Manager, PhysicalPosition, PhysicalSize, Position, Size, WebviewWindow};
+
+use crate::{
+    client::MediaClient,
+    error::MediaError,
+    get_workspace_height,
+    ipc::{self, IpcRequest, IpcResponse},
+    model::SessionControl,
+    settings::{SettingsState, WindowSettings},
+};
+
+const MAIN_WINDOW: &str = "main";
+
+// what a launch can ask of the running instance, e.g. from a desktop shortcut:
+// --show, --toggle-mini and --control play|pause|toggle|next|prev
+#[derive(Debug, Clone)]
+pub enum InstanceAction {
+    Show,
+    ToggleMini,
+    Control(SessionControl),
+}
+
+pub fn parse_args(args: &[String]) -> Result<Vec<InstanceAction>, MediaError> {
+    let mut actions = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let action = match arg.as_str() {
+            "--show" => InstanceAction::Show,
+            "--toggle-mini" => InstanceAction::ToggleMini,
+            "--control" => {
+                let control = match args.next().map(|a| a.as_str()) {
+                    Some("play") => SessionControl::Play,
+                    Some("pause") => SessionControl::Pause,
+                    Some("toggle") => SessionControl::TogglePlayPause,
+                    Some("next") => SessionControl::SkipNext,
+                    Some("prev") => SessionControl::SkipPrevious,
+                    other => {
+                        return Err(MediaError::conversion_failed(format!(
+                            "--control expects play, pause, toggle, next or prev, got {}.",
+                            other.unwrap_or("nothing")
+                        )));
+                    }
+                };
+                InstanceAction::Control(control)
+            }
+            other => {
+                return Err(MediaError::conversion_failed(format!(
+                    "Unknown argument {other}."
+                )));
+            }
+        };
+        actions.push(action);
+    }
+    Ok(actions)
+}
+
+// hands the arguments of a second launch to the running instance
+pub fn forward(args: Vec<String>) -> Result<(), MediaError> {
+    match ipc::request(&IpcRequest::Forward { args })? {
+        IpcResponse::Error(err) => Err(MediaError::new(err.kind, err.message)),
+        _ => Ok(()),
+    }
+}
+
+// runs in the first instance, for its own arguments as well as forwarded ones. the first launch
+// runs before any player was found, so its controls wait for the first active session instead
+pub fn run_args(handle: &AppHandle, args: &[String], first_launch: bool) -> Result<(), MediaError> {
+    for action in parse_args(args)? {
+        match action {
+            InstanceAction::Show => show(handle)?,
+            InstanceAction::ToggleMini => toggle_mini(handle)?,
+            InstanceAction::Control(control) => {
+                let client = handle.state::<MediaClient>();
+                let active = client.store.lock().unwrap().active().map(|s| s.session_id);
+                match active {
+                    Some(session_id) => client.control_session(session_id, control)?,
+                    None if first_launch => client.queue_control(control),
+                    None => return Err(MediaError::not_found("No session is active.")),
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn window_error(err: tauri::Error) -> MediaError {
+    MediaError::control_rejected("Failed to update the window.").with_source(err)
+}
+
+pub(crate) fn show(handle: &AppHandle) -> Result<(), MediaError> {
+    let Some(window) = handle.get_webview_window(MAIN_WINDOW) else {
+        return Err(MediaError::not_found("The window is closed."));
+    };
+    window.unminimize().map_err(window_error)?;
+    window.show().map_err(window_error)?;
+    window.set_focus().map_err(window_error)
+}
+
+pub(crate) fn toggle_window(handle: &AppHandle) -> Result<(), MediaError> {
+    let Some(window) = handle.get_webview_window(MAIN_WINDOW) else {
+        return Err(MediaError::not_found("The window is closed."));
+    };
+    let visible = window.is_visible().map_err(window_error)?;
+    let minimized = window.is_minimized().map_err(window_error)?;
+    if visible && !minimized {
+        window.hide().map_err(window_error)
+    } else {
+        show(handle)
+    }
+}
+
+fn workspace_height(window: &WebviewWindow) -> Result<i32, MediaError> {
+    match get_workspace_height(&window.as_ref().window()) {
+        Ok(h) => Ok(h),
+        Err(err) => Err(
+            MediaError::control_rejected("Failed to find the work area.")
+                .with_source(err.to_string()),
+        ),
+    }
+}
+
+// mini mode follows the window position, so the window is moved to or away from the bottom edge
+// and the move handler does the rest
+pub(crate) fn toggle_mini(handle: &AppHandle) -> Result<(), MediaError> {
+    let Some(window) = handle.get_webview_window(MAIN_WINDOW) else {
+        return Err(MediaError::not_found("The window is closed."));
+    };
+    let settings = handle.state::<SettingsState>().window();
+    let edge = settings.mini_mode_edge as i32;
+    let workspace_height = workspace_height(&window)?;
+    let position = window.outer_position().map_err(window_error)?;
+    let y = if position.y > workspace_height - edge {
+        workspace_height - edge - settings.normal_height as i32
+    } else {
+        workspace_height - edge + 1
+    };
+    window
+        .set_position(Position::Physical(PhysicalPosition { x: position.x, y }))
+        .map_err(window_error)
+}
+
+// resizes the window for the mode it is in, on startup and when the settings change
+pub(crate) fn apply_window_settings(
+    handle: &AppHandle,
+    settings: &WindowSettings,
+) -> Result<(), MediaError> {
+    let Some(window) = handle.get_webview_window(MAIN_WINDOW) else {
+        return Err(MediaError::not_found("The window is closed."));
+    };
+    let workspace_height = workspace_height(&window)?;
+    let position = window.outer_position().map_err(window_error)?;
+    let (width, height) = if position.y > workspace_height - settings.mini_mode_edge as i32 {
+        (settings.mini_width, settings.mini_height)
+    } else {
+        window
+            .set_always_on_top(settings.always_on_top)
+            .map_err(window_error)?;
+        (settings.normal_width, settings.normal_height)
+    };
+    window
+        .set_size(Size::Physical(PhysicalSize { width, height }))
+        .map_err(window_error)
+}
@@ -0,0 +1,229 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    thread,
+};
+
+#[cfg(unix)]
+use std::path::PathBuf;
+
+use interprocess::local_socket::{prelude::*, ListenerOptions, Name};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    api::{api_session, ApiSession},
+    client::MediaClient,
+    error::{ErrorKind, MediaError},
+    instance,
+    model::SessionControl,
+};
+
+// a unix socket in the runtime directory, or a named pipe on windows
+pub const IPC_SOCKET_NAME: &str = "now-playing.sock";
+
+// one json request per line, each answered with one json response line
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "command", rename_all = "camelCase")]
+pub enum IpcRequest {
+    GetCurrentSessions,
+    ControlSession {
+        // the active session when None
+        #[serde(rename = "sessionId")]
+        session_id: Option<usize>,
+        control: SessionControl,
+    },
+    // the arguments of a second launch, see instance.rs
+    Forward {
+        args: Vec<String>,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IpcSessions {
+    pub sessions: Vec<ApiSession>,
+    pub active_session_id: Option<usize>,
+}
+
+// the kind and message of a MediaError, which is only ever serialized on the app side
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct IpcError {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub enum IpcResponse {
+    Sessions(IpcSessions),
+    Done,
+    Error(IpcError),
+}
+
+impl From<MediaError> for IpcResponse {
+    fn from(err: MediaError) -> Self {
+        IpcResponse::Error(IpcError {
+            kind: err.kind,
+            message: err.message,
+        })
+    }
+}
+
+#[cfg(unix)]
+fn socket_path() -> PathBuf {
+    // the runtime directory is private to the user, the temp directory is not
+    match std::env::var_os("XDG_RUNTIME_DIR") {
+        Some(dir) => PathBuf::from(dir).join(IPC_SOCKET_NAME),
+        None => {
+            let user = std::env::var("USER").unwrap_or_default();
+            std::env::temp_dir().join(format!("{user}-{IPC_SOCKET_NAME}"))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericFilePath;
+
+    socket_path().to_fs_name::<GenericFilePath>()
+}
+
+#[cfg(windows)]
+fn socket_name() -> io::Result<Name<'static>> {
+    use interprocess::local_socket::GenericNamespaced;
+
+    IPC_SOCKET_NAME.to_ns_name::<GenericNamespaced>()
+}
+
+pub enum Instance {
+    // this process owns the socket and should start the app
+    First(LocalSocketListener),
+    // another process answers on the socket
+    Running,
+}
+
+// whoever binds the socket first is the single instance
+pub fn claim_instance() -> Result<Instance, MediaError> {
+    match bind() {
+        Ok(Some(listener)) => Ok(Instance::First(listener)),
+        Ok(None) => Ok(Instance::Running),
+        Err(err) => Err(
+            MediaError::service_failed("Failed to open the command line socket.").with_source(err),
+        ),
+    }
+}
+
+// the listener lives as long as the app, answering every connection on a thread of its own
+pub fn start_server(handle: AppHandle, listener: LocalSocketListener) {
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(err) => {
+                    println!("[ipc] {}", err);
+                    continue;
+                }
+            };
+            let handle = handle.clone();
+            thread::spawn(move || {
+                if let Err(err) = serve(&handle, stream) {
+                    println!("[ipc] {}", err);
+                }
+            });
+        }
+    });
+}
+
+// None when another instance is listening
+fn bind() -> io::Result<Option<LocalSocketListener>> {
+    match ListenerOptions::new().name(socket_name()?).create_sync() {
+        Ok(listener) => Ok(Some(listener)),
+        Err(err) if err.kind() == io::ErrorKind::AddrInUse => {
+            // a socket file left behind by a crash is taken over, a live one is not
+            if LocalSocketStream::connect(socket_name()?).is_ok() {
+                return Ok(None);
+            }
+            #[cfg(unix)]
+            std::fs::remove_file(socket_path())?;
+            ListenerOptions::new()
+                .name(socket_name()?)
+                .create_sync()
+                .map(Some)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+fn serve(handle: &AppHandle, stream: LocalSocketStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let response = match serde_json::from_str::<IpcRequest>(&line) {
+            Ok(request) => respond(handle, request),
+            Err(err) => MediaError::conversion_failed(format!("Invalid request: {err}.")).into(),
+        };
+        let mut data = serde_json::to_vec(&response)?;
+        data.push(b'\n');
+        (&stream).write_all(&data)?;
+    }
+}
+
+fn respond(handle: &AppHandle, request: IpcRequest) -> IpcResponse {
+    let client = handle.state::<MediaClient>();
+    match request {
+        IpcRequest::GetCurrentSessions => {
+            let active_session_id = client.store.lock().unwrap().active().map(|s| s.session_id);
+            IpcResponse::Sessions(IpcSessions {
+                sessions: client
+                    .get_current_sessions()
+                    .into_iter()
+                    .map(|s| api_session(&client, s))
+                    .collect(),
+                active_session_id,
+            })
+        }
+        IpcRequest::ControlSession {
+            session_id,
+            control,
+        } => {
+            let active_session_id = client.store.lock().unwrap().active().map(|s| s.session_id);
+            let Some(session_id) = session_id.or(active_session_id) else {
+                return MediaError::not_found("No session is active.").into();
+            };
+            match client.control_session(session_id, control) {
+                Ok(()) => IpcResponse::Done,
+                Err(err) => err.into(),
+            }
+        }
+        IpcRequest::Forward { args } => match instance::run_args(handle, &args, false) {
+            Ok(()) => IpcResponse::Done,
+            Err(err) => err.into(),
+        },
+    }
+}
+
+// what the command line uses, one connection per request
+pub fn request(request: &IpcRequest) -> Result<IpcResponse, MediaError> {
+    let result = socket_name()
+        .and_then(LocalSocketStream::connect)
+        .and_then(|stream| {
+            let mut data = serde_json::to_vec(request)?;
+            data.push(b'\n');
+            (&stream).write_all(&data)?;
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line)?;
+            Ok(serde_json::from_str::<IpcResponse>(&line)?)
+        });
+    match result {
+        Ok(r) => Ok(r),
+        Err(err) => Err(MediaError::backend_unavailable(
+            "Failed to reach now-playing, is it running?",
+        )
+        .with_source(err)),
+    }
+}
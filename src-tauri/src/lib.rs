@@ -1,46 +1,127 @@
 use std::sync::{Arc, Mutex as SyncMutex};
 
+use api::feed::EventFeed;
+use artwork::{ArtworkCache, ARTWORK_DIR_NAME};
+use backend::UnavailableBackend;
+use capture::recorder::Recorder;
+use client::MediaClient;
+use error::MediaError;
+use history::{HistoryEntry, HistoryRange, HISTORY_FILE_NAME};
+use hotkey::{HotkeyBinding, Hotkeys};
+use ipc::Instance;
+use model::{Artwork, CurrentSession, SessionControl};
+use position::SessionPosition;
+use protocol::{artwork_response, ART_PROTOCOL};
+use scrobble::SCROBBLE_QUEUE_FILE_NAME;
 use serde::Serialize;
-use tauri::{
-    async_runtime::Mutex, AppHandle, Emitter, Manager, PhysicalSize, Size, State, WindowEvent,
-};
-use windows::Win32::{
-    Foundation::RECT,
-    UI::WindowsAndMessaging::{SystemParametersInfoA, SPI_GETWORKAREA},
-};
-use winrt::{
-    error::WinRTError,
-    media::MediaClient,
-    model::{CurrentSession, SessionControl},
-};
+use services::Services;
+use settings::{Settings, SettingsState, SettingsView, SETTINGS_FILE_NAME};
+use tauri::{AppHandle, Emitter, Manager, PhysicalSize, Size, State, Window, WindowEvent};
 
+pub mod api;
+pub mod artwork;
+pub mod backend;
+pub mod capture;
+pub mod client;
+pub mod discord;
+pub mod error;
+pub mod history;
+pub mod hotkey;
+pub mod instance;
+pub mod ipc;
+pub mod mock;
+pub mod model;
+#[cfg(target_os = "linux")]
+pub mod mpris;
+pub mod output;
+pub mod palette;
+pub mod position;
+pub mod protocol;
+pub mod scrobble;
+pub mod services;
+pub mod settings;
+pub mod store;
+pub mod template;
+pub mod thumbnail;
+#[cfg(windows)]
 pub mod winrt;
 
 #[tauri::command]
 async fn get_current_sessions(
-    media_client: State<'_, Mutex<MediaClient>>,
-) -> Result<Vec<CurrentSession>, WinRTError> {
-    let client = media_client.lock().await;
-    let sessions = client.get_current_sessions().unwrap();
-    Ok(sessions)
+    media_client: State<'_, MediaClient>,
+) -> Result<Vec<CurrentSession>, MediaError> {
+    Ok(media_client.get_current_sessions())
+}
+
+#[tauri::command]
+async fn get_position(
+    media_client: State<'_, MediaClient>,
+    session_id: usize,
+) -> Result<SessionPosition, MediaError> {
+    media_client.get_position(session_id)
+}
+
+#[tauri::command]
+async fn get_artwork(
+    media_client: State<'_, MediaClient>,
+    art_id: String,
+) -> Result<Artwork, MediaError> {
+    media_client.get_artwork(&art_id)
+}
+
+#[tauri::command]
+async fn get_history(
+    media_client: State<'_, MediaClient>,
+    range: Option<HistoryRange>,
+    limit: Option<usize>,
+) -> Result<Vec<HistoryEntry>, MediaError> {
+    Ok(media_client.get_history(range.unwrap_or_default(), limit))
+}
+
+#[tauri::command]
+async fn get_hotkeys(hotkeys: State<'_, Hotkeys>) -> Result<Vec<HotkeyBinding>, MediaError> {
+    Ok(hotkeys.bindings())
+}
+
+#[tauri::command]
+async fn get_settings(settings: State<'_, SettingsState>) -> Result<SettingsView, MediaError> {
+    Ok(settings.get().view())
+}
+
+#[tauri::command]
+async fn update_settings(
+    handle: AppHandle,
+    settings: SettingsView,
+) -> Result<SettingsView, MediaError> {
+    settings::update(&handle, settings)
 }
 
 #[tauri::command]
 async fn control_session(
-    media_client: State<'_, Mutex<MediaClient>>,
-    source: String,
+    media_client: State<'_, MediaClient>,
+    session_id: usize,
     control: SessionControl,
-) -> Result<(), WinRTError> {
-    let client = media_client.lock().await;
-    client.control_session(source, control).unwrap();
-    Ok(())
+) -> Result<(), MediaError> {
+    media_client.control_session(session_id, control)
 }
 
 pub fn emit_event<S: Serialize + Clone>(event_name: &str, payload: S, handle: &AppHandle) {
-    handle.emit(event_name, payload).unwrap();
+    // only managed once setup ran, without websockets connected to the api this does nothing
+    if let Some(feed) = handle.try_state::<Arc<EventFeed>>() {
+        feed.publish(event_name, &payload);
+    }
+    if let Err(err) = handle.emit(event_name, payload) {
+        println!("Failed to emit {event_name}.\n{}", err);
+    }
 }
 
-fn get_workspace_height() -> Result<i32, Box<dyn std::error::Error>> {
+#[cfg(windows)]
+fn get_workspace_height(_window: &Window) -> Result<i32, Box<dyn std::error::Error>> {
+    use windows::Win32::{
+        Foundation::RECT,
+        UI::WindowsAndMessaging::{SystemParametersInfoA, SPI_GETWORKAREA},
+    };
+
     let mut rect: RECT = Default::default();
     unsafe {
         SystemParametersInfoA(
@@ -48,35 +129,76 @@ fn get_workspace_height() -> Result<i32, Box<dyn std::error::Error>> {
             0,
             Some(&mut rect as *mut _ as _),
             Default::default(),
-        )
-        .unwrap();
+        )?;
     }
     Ok(rect.bottom)
 }
 
+#[cfg(not(windows))]
+fn get_workspace_height(window: &Window) -> Result<i32, Box<dyn std::error::Error>> {
+    // there is no portable work area api, so the bottom edge of the monitor is used instead
+    let Some(monitor) = window.current_monitor()? else {
+        return Err("Failed to find the current monitor.".into());
+    };
+    Ok(monitor.position().y + monitor.size().height as i32)
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
-    let workspace_height = get_workspace_height().unwrap();
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    // a second launch only passes its arguments on, so there is one widget and one event loop
+    let listener = match ipc::claim_instance() {
+        Ok(Instance::First(listener)) => Some(listener),
+        Ok(Instance::Running) => {
+            if let Err(err) = instance::forward(args) {
+                println!("{}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Err(err) => {
+            println!("{}", err);
+            None
+        }
+    };
     let is_mini = Arc::new(SyncMutex::new(false));
     tauri::Builder::default()
         .plugin(tauri_plugin_process::init())
         .plugin(tauri_plugin_shell::init())
         .invoke_handler(tauri::generate_handler![
             get_current_sessions,
+            get_position,
+            get_artwork,
+            get_history,
+            get_hotkeys,
+            get_settings,
+            update_settings,
             control_session
         ])
+        .register_uri_scheme_protocol(ART_PROTOCOL, |ctx, request| {
+            let artwork = ctx.app_handle().try_state::<Arc<ArtworkCache>>();
+            artwork_response(artwork.as_deref().map(|a| a.as_ref()), &request)
+        })
         .on_window_event(move |window, event| match event {
             WindowEvent::Moved(pos) => {
+                let Ok(workspace_height) = get_workspace_height(window) else {
+                    return;
+                };
+                // managed in setup, which can run after the first events
+                let settings = window
+                    .try_state::<SettingsState>()
+                    .map(|s| s.window())
+                    .unwrap_or_default();
                 let mini_arc = Arc::clone(&is_mini);
                 let mut mini = mini_arc.lock().unwrap();
-                if pos.y > workspace_height - 50 {
+                if pos.y > workspace_height - settings.mini_mode_edge as i32 {
                     if *mini == false {
                         println!("changed to mini mode");
                         window.set_always_on_top(true).unwrap();
                         window
                             .set_size(Size::Physical(PhysicalSize {
-                                width: 500,
-                                height: 50,
+                                width: settings.mini_width,
+                                height: settings.mini_height,
                             }))
                             .unwrap();
                         window.emit("mini_mode", true).unwrap();
@@ -85,11 +207,11 @@ pub fn run() {
                 } else {
                     if *mini == true {
                         println!("changed to normal mode");
-                        window.set_always_on_top(false).unwrap();
+                        window.set_always_on_top(settings.always_on_top).unwrap();
                         window
                             .set_size(Size::Physical(PhysicalSize {
-                                width: 300,
-                                height: 300,
+                                width: settings.normal_width,
+                                height: settings.normal_height,
                             }))
                             .unwrap();
                         window.emit("mini_mode", false).unwrap();
@@ -99,18 +221,110 @@ pub fn run() {
             }
             _ => {}
         })
-        .setup(|app| {
+        .setup(move |app| {
             let app_handle = app.handle();
-            let media_client = MediaClient::new().unwrap();
-            let media_client_state = Mutex::from(media_client);
-            app.manage(media_client_state);
+            let settings_file = match app.path().app_config_dir() {
+                Ok(dir) => Some(dir.join(SETTINGS_FILE_NAME)),
+                Err(err) => {
+                    println!("{}", err);
+                    None
+                }
+            };
+            let settings = SettingsState::open(settings_file.clone());
+            let initial = settings.get();
+            app.manage(settings);
+            let media_client = MediaClient::new().unwrap_or_else(|err| {
+                // keep the window up, commands will report the reason to the frontend
+                println!("{}", err);
+                MediaClient::with_backend(Box::new(UnavailableBackend {
+                    reason: err.to_string(),
+                }))
+            });
+            let media_client = media_client.with_thumbnails(initial.thumbnails.clone());
+            let media_client = match app.path().app_cache_dir() {
+                Ok(dir) => media_client.with_artwork_dir(dir.join(ARTWORK_DIR_NAME)),
+                Err(err) => {
+                    println!("{}", err);
+                    media_client
+                }
+            };
+            let data_dir = match app.path().app_data_dir() {
+                Ok(dir) => Some(dir),
+                Err(err) => {
+                    println!("{}", err);
+                    None
+                }
+            };
+            let media_client = match data_dir.as_ref() {
+                Some(dir) => media_client.with_history_file(dir.join(HISTORY_FILE_NAME)),
+                None => media_client,
+            };
+            let events = media_client.subscribe();
+            let store = Arc::clone(&media_client.store);
+            let artwork = Arc::clone(&media_client.artwork);
+            let history = Arc::clone(&media_client.history);
+            app.manage(Arc::clone(&artwork));
+            app.manage(media_client);
+            if let Some(listener) = listener {
+                ipc::start_server(app_handle.clone(), listener);
+            }
+            if let Err(err) = instance::apply_window_settings(app_handle, &initial.window) {
+                println!("{}", err);
+            }
+            if let Some(file) = settings_file {
+                if let Err(err) = settings::watch(app_handle.clone(), file) {
+                    println!("{}", err);
+                }
+            }
+            match Hotkeys::start(app_handle.clone(), initial.hotkeys.clone()) {
+                Ok(h) => {
+                    app.manage(h);
+                }
+                Err(err) => println!("{}", err),
+            }
+            // the first launch takes the same arguments it would forward
+            if let Err(err) = instance::run_args(app_handle, &args, true) {
+                println!("{}", err);
+            }
+            // managed before the services, the api serves the feed
+            app.manage(Arc::new(EventFeed::new(Arc::clone(&store))));
+            let services = Arc::new(SyncMutex::new(Services::new(
+                data_dir.map(|d| d.join(SCROBBLE_QUEUE_FILE_NAME)),
+            )));
+            services
+                .lock()
+                .unwrap()
+                .configure(app_handle, &Settings::default(), &initial);
+            app.manage(Arc::clone(&services));
 
+            let events = match events {
+                Ok(e) => e,
+                Err(err) => {
+                    println!("{}", err);
+                    return Ok(());
+                }
+            };
             // credit: https://sneakycrow.dev/blog/2024-05-12-running-async-tasks-in-tauri-v2
+            let ticker_handle = app_handle.clone();
+            let ticker_store = Arc::clone(&store);
+            tauri::async_runtime::spawn(async move {
+                MediaClient::init_position_ticker(&ticker_handle, ticker_store).await;
+            });
             let app_handle_clone = app_handle.clone().to_owned();
             tauri::async_runtime::spawn(async move {
-                MediaClient::init_event_handler(&app_handle_clone)
-                    .await
-                    .unwrap();
+                if let Err(err) = MediaClient::init_event_handler(
+                    &app_handle_clone,
+                    store,
+                    events,
+                    Recorder::from_env(),
+                    artwork,
+                    history,
+                    services,
+                )
+                .await
+                {
+                    println!("{}", err);
+                }
             });
             Ok(())
         })
@@ -0,0 +1,375 @@
+use std::{
+    collections::BTreeMap,
+    path::Path,
+    sync::{Arc, Mutex as SyncMutex},
+    time::Duration,
+};
+
+use tauri::async_runtime::{channel, Receiver, Sender};
+use tokio::time::{sleep_until, Instant};
+
+use crate::{
+    backend::{BackendEvent, MediaBackend},
+    error::MediaError,
+    model::{
+        ActiveSessionChange, PlaybackStatus, SessionControl, SessionCreate, SessionModel,
+        SessionRemove, SessionUpdate, TICKS_PER_MILLISECOND,
+    },
+    position::now_ms,
+};
+
+use super::script::{Script, ScriptEvent};
+
+// FastForward and Rewind move the simulated position by 10 seconds (in 100ns ticks)
+const SEEK_STEP: i64 = 100_000_000;
+
+struct MockSession {
+    source: String,
+    model: SessionModel,
+}
+
+#[derive(Default)]
+struct MockState {
+    sessions: BTreeMap<usize, MockSession>,
+    // set once subscribed, so that controls can report the state they changed
+    tx: Option<Sender<BackendEvent>>,
+}
+
+pub struct MockBackend {
+    script: Script,
+    state: Arc<SyncMutex<MockState>>,
+}
+
+impl MockBackend {
+    pub fn new(script: Script) -> Self {
+        Self {
+            script,
+            state: Arc::new(SyncMutex::new(MockState::default())),
+        }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MediaError> {
+        let script = Script::from_file(path)?;
+        Ok(Self::new(script))
+    }
+
+    fn apply(state: &mut MockState, event: ScriptEvent) -> Option<BackendEvent> {
+        match event {
+            ScriptEvent::SessionCreate { session_id, source } => {
+                state.sessions.insert(
+                    session_id,
+                    MockSession {
+                        source: source.clone(),
+                        model: SessionModel {
+                            playback: None,
+                            timeline: None,
+                            media: None,
+                            source: source.clone(),
+                        },
+                    },
+                );
+                Some(BackendEvent::SessionCreate(SessionCreate {
+                    session_id,
+                    source,
+                }))
+            }
+            ScriptEvent::SessionUpdate {
+                session_id,
+                playback,
+                timeline,
+                media,
+            } => {
+                let Some(session) = state.sessions.get_mut(&session_id) else {
+                    println!("[mock] update for unknown session {session_id}");
+                    return None;
+                };
+                if playback.is_some() {
+                    session.model.playback = playback;
+                }
+                if let Some(mut timeline) = timeline {
+                    timeline.last_updated_at_ms += now_ms();
+                    session.model.timeline = Some(timeline);
+                }
+                if media.is_some() {
+                    session.model.media = media;
+                }
+                Some(BackendEvent::SessionUpdate(SessionUpdate {
+                    session_id,
+                    source: session.source.clone(),
+                    session_model: session.model.clone(),
+                    image: None,
+                    art_id: None,
+                    palette: None,
+                }))
+            }
+            ScriptEvent::Thumbnail {
+                session_id, data, ..
+            } => {
+                let Some(session) = state.sessions.get(&session_id) else {
+                    println!("[mock] thumbnail for unknown session {session_id}");
+                    return None;
+                };
+                Some(BackendEvent::SessionUpdate(SessionUpdate {
+                    session_id,
+                    source: session.source.clone(),
+                    session_model: session.model.clone(),
+                    image: data,
+                    art_id: None,
+                    palette: None,
+                }))
+            }
+            ScriptEvent::SessionRemove { session_id } => {
+                state.sessions.remove(&session_id)?;
+                Some(BackendEvent::SessionRemove(SessionRemove { session_id }))
+            }
+            ScriptEvent::CurrentSessionChange { session_id } => {
+                Some(BackendEvent::ActiveSessionChange(
+                    session_id.map(|id| ActiveSessionChange { session_id: id }),
+                ))
+            }
+        }
+    }
+
+    async fn replay(script: Script, state: Arc<SyncMutex<MockState>>, tx: Sender<BackendEvent>) {
+        loop {
+            let start = Instant::now();
+            for entry in script.events.iter() {
+                sleep_until(start + Duration::from_millis(entry.at_ms)).await;
+                let event = {
+                    let mut state = state.lock().unwrap();
+                    MockBackend::apply(&mut state, entry.event.clone())
+                };
+                if let Some(event) = event {
+                    if tx.send(event).await.is_err() {
+                        return;
+                    }
+                }
+            }
+            if !script.repeat || script.events.is_empty() {
+                break;
+            }
+        }
+        println!("[mock] finished replaying script");
+    }
+
+    // returns false when the session has nothing to control yet
+    fn apply_control(session: &mut MockSession, control: &SessionControl) -> bool {
+        // the position is only true at its timestamp, so it is moved up to now before a control
+        // changes the status or rate it advances with
+        if let Some(timeline) = session.model.timeline.as_mut() {
+            let now = now_ms();
+            let playing = session
+                .model
+                .playback
+                .as_ref()
+                .filter(|p| p.status == PlaybackStatus::Playing);
+            if let (Some(playback), true) = (playing, timeline.last_updated_at_ms > 0) {
+                let elapsed_ms = (now - timeline.last_updated_at_ms).max(0) as f64;
+                timeline.position +=
+                    (elapsed_ms * TICKS_PER_MILLISECOND as f64 * playback.rate) as i64;
+                if timeline.end > timeline.start {
+                    timeline.position = timeline.position.min(timeline.end).max(timeline.start);
+                }
+            }
+            timeline.last_updated_at_ms = now;
+        }
+        let Some(playback) = session.model.playback.as_mut() else {
+            return false;
+        };
+        match control {
+            SessionControl::Play => playback.status = PlaybackStatus::Playing,
+            SessionControl::Pause => playback.status = PlaybackStatus::Paused,
+            SessionControl::TogglePlayPause => {
+                playback.status = match playback.status {
+                    PlaybackStatus::Playing => PlaybackStatus::Paused,
+                    _ => PlaybackStatus::Playing,
+                };
+            }
+            SessionControl::ToggleShuffle => playback.shuffle = !playback.shuffle,
+            SessionControl::SetShuffle(shuffle) => playback.shuffle = *shuffle,
+            SessionControl::CycleRepeat => playback.auto_repeat = playback.auto_repeat.cycle(),
+            SessionControl::SetRepeat(mode) => playback.auto_repeat = *mode,
+            SessionControl::SetRate(rate) => {
+                if !(rate.is_finite() && *rate > 0.0) {
+                    return false;
+                }
+                playback.rate = *rate;
+            }
+            SessionControl::FastForward
+            | SessionControl::Rewind
+            | SessionControl::SkipNext
+            | SessionControl::SkipPrevious
+            | SessionControl::SeekTo(_)
+            | SessionControl::SeekBy(_) => {
+                let Some(timeline) = session.model.timeline.as_mut() else {
+                    return false;
+                };
+                let position = match control {
+                    SessionControl::FastForward => timeline.position + SEEK_STEP,
+                    SessionControl::Rewind => timeline.position - SEEK_STEP,
+                    SessionControl::SeekTo(position) => *position,
+                    SessionControl::SeekBy(offset) => timeline.position + offset,
+                    // there is no playlist to move through, so skipping restarts the track
+                    _ => timeline.start,
+                };
+                timeline.position = position.min(timeline.end).max(timeline.start);
+            }
+        }
+        true
+    }
+}
+
+impl MediaBackend for MockBackend {
+    fn control_session(
+        &self,
+        session_id: usize,
+        control: SessionControl,
+    ) -> Result<(), MediaError> {
+        let mut state = self.state.lock().unwrap();
+        let Some(session) = state.sessions.get_mut(&session_id) else {
+            return Err(
+                MediaError::not_found(format!("Session {session_id} no longer exists."))
+                    .with_session(session_id),
+            );
+        };
+        if !MockBackend::apply_control(session, &control) {
+            return Err(MediaError::control_rejected(format!(
+                "Failed to control {} session: {:?}",
+                session.source, control
+            ))
+            .with_session(session_id));
+        }
+        let update = SessionUpdate {
+            session_id,
+            source: session.source.clone(),
+            session_model: session.model.clone(),
+            image: None,
+            art_id: None,
+            palette: None,
+        };
+        if let Some(tx) = state.tx.as_ref() {
+            tx.try_send(BackendEvent::SessionUpdate(update)).ok();
+        }
+        Ok(())
+    }
+
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        let (tx, rx) = channel(64);
+        self.state.lock().unwrap().tx = Some(tx.clone());
+        tauri::async_runtime::spawn(MockBackend::replay(
+            self.script.clone(),
+            Arc::clone(&self.state),
+            tx,
+        ));
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::{test_session, TimelineModel};
+
+    use super::*;
+
+    fn paused_session(position: i64) -> MockSession {
+        let mut model = test_session("Song", "Band");
+        model.playback.as_mut().unwrap().status = PlaybackStatus::Paused;
+        model.timeline = Some(TimelineModel {
+            start: 0,
+            end: 3 * SEEK_STEP,
+            position,
+            last_updated_at_ms: 0,
+        });
+        MockSession {
+            source: model.source.clone(),
+            model,
+        }
+    }
+
+    fn position(session: &MockSession) -> i64 {
+        session.model.timeline.as_ref().unwrap().position
+    }
+
+    #[test]
+    fn controls_stay_within_the_timeline() {
+        let mut session = paused_session(2 * SEEK_STEP);
+        assert!(MockBackend::apply_control(
+            &mut session,
+            &SessionControl::FastForward
+        ));
+        assert!(MockBackend::apply_control(
+            &mut session,
+            &SessionControl::FastForward
+        ));
+        assert_eq!(position(&session), 3 * SEEK_STEP);
+
+        assert!(MockBackend::apply_control(
+            &mut session,
+            &SessionControl::SeekBy(-10 * SEEK_STEP)
+        ));
+        assert_eq!(position(&session), 0);
+
+        assert!(!MockBackend::apply_control(
+            &mut session,
+            &SessionControl::SetRate(0.0)
+        ));
+        session.model.timeline = None;
+        assert!(!MockBackend::apply_control(
+            &mut session,
+            &SessionControl::SkipNext
+        ));
+    }
+
+    fn next_event(rx: &mut Receiver<BackendEvent>) -> BackendEvent {
+        let event = tauri::async_runtime::block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+        });
+        match event {
+            Ok(Some(event)) => event,
+            _ => panic!("the backend sent no event"),
+        }
+    }
+
+    #[test]
+    fn replays_a_script_and_answers_controls() {
+        let script = serde_json::from_str::<Script>(
+            r#"{"events": [
+                {"atMs": 0, "type": "session_create", "sessionId": 1, "source": "Mock.exe"},
+                {"atMs": 0, "type": "session_update", "sessionId": 1,
+                 "playback": {"status": "Paused", "type": "Music", "rate": 1.0,
+                              "shuffle": false, "autoRepeat": "None"},
+                 "timeline": {"start": 0, "end": 600000000, "position": 0,
+                              "lastUpdatedAtMs": 0}},
+                {"atMs": 0, "type": "current_session_change", "sessionId": 1}
+            ]}"#,
+        )
+        .unwrap();
+        let backend = MockBackend::new(script);
+        let mut rx = backend.subscribe().unwrap();
+        let started_ms = now_ms();
+
+        assert!(matches!(
+            next_event(&mut rx),
+            BackendEvent::SessionCreate(SessionCreate { session_id: 1, .. })
+        ));
+        let BackendEvent::SessionUpdate(update) = next_event(&mut rx) else {
+            panic!("expected an update");
+        };
+        // the script's timestamp counts from when the update was sent
+        let timeline = update.session_model.timeline.unwrap();
+        assert!(timeline.last_updated_at_ms >= started_ms);
+        assert!(matches!(
+            next_event(&mut rx),
+            BackendEvent::ActiveSessionChange(Some(ActiveSessionChange { session_id: 1 }))
+        ));
+
+        backend
+            .control_session(1, SessionControl::SeekTo(SEEK_STEP))
+            .unwrap();
+        let BackendEvent::SessionUpdate(update) = next_event(&mut rx) else {
+            panic!("expected an update");
+        };
+        assert_eq!(update.session_model.timeline.unwrap().position, SEEK_STEP);
+        assert!(backend.control_session(2, SessionControl::Play).is_err());
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::MediaError,
+    model::{MediaModel, PlaybackModel, TimelineModel},
+};
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Script {
+    pub events: Vec<ScriptEntry>,
+    // replays the events from the beginning once the last one has been sent
+    #[serde(default)]
+    pub repeat: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ScriptEntry {
+    // offset from the start of the replay
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub event: ScriptEvent,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ScriptEvent {
+    #[serde(rename_all = "camelCase")]
+    SessionCreate { session_id: usize, source: String },
+    // fields that are left out keep their previous value. the timeline's lastUpdatedAtMs is
+    // relative to when the update is sent, so 0 is a position reported right then
+    #[serde(rename_all = "camelCase")]
+    SessionUpdate {
+        session_id: usize,
+        playback: Option<PlaybackModel>,
+        timeline: Option<TimelineModel>,
+        media: Option<MediaModel>,
+    },
+    // either inline bytes or a path relative to the script file
+    #[serde(rename_all = "camelCase")]
+    Thumbnail {
+        session_id: usize,
+        data: Option<Vec<u8>>,
+        path: Option<PathBuf>,
+    },
+    #[serde(rename_all = "camelCase")]
+    SessionRemove { session_id: usize },
+    #[serde(rename_all = "camelCase")]
+    CurrentSessionChange { session_id: Option<usize> },
+}
+
+impl Script {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, MediaError> {
+        let path = path.as_ref();
+        let content = match std::fs::read_to_string(path) {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(MediaError::backend_unavailable(format!(
+                    "Failed to read mock script {}.",
+                    path.display()
+                ))
+                .with_source(err));
+            }
+        };
+        let is_yaml = matches!(
+            path.extension().and_then(|e| e.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        let parsed = if is_yaml {
+            serde_yaml::from_str::<Script>(&content).map_err(|e| e.to_string())
+        } else {
+            serde_json::from_str::<Script>(&content).map_err(|e| e.to_string())
+        };
+        let mut script = match parsed {
+            Ok(s) => s,
+            Err(err) => {
+                return Err(MediaError::conversion_failed(format!(
+                    "Failed to parse mock script {}.",
+                    path.display()
+                ))
+                .with_source(err));
+            }
+        };
+
+        // thumbnails are loaded up front so that replay timing does not depend on disk access
+        let base_dir = path.parent().unwrap_or(Path::new("."));
+        for entry in script.events.iter_mut() {
+            if let ScriptEvent::Thumbnail {
+                data: data @ None,
+                path: Some(thumbnail_path),
+                ..
+            } = &mut entry.event
+            {
+                let full_path = base_dir.join(&thumbnail_path);
+                match std::fs::read(&full_path) {
+                    Ok(bytes) => *data = Some(bytes),
+                    Err(err) => {
+                        return Err(MediaError::backend_unavailable(format!(
+                            "Failed to read thumbnail {}.",
+                            full_path.display()
+                        ))
+                        .with_source(err));
+                    }
+                }
+            }
+        }
+        script.events.sort_by_key(|e| e.at_ms);
+        Ok(script)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::error::ErrorKind;
+
+    fn script_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("now-playing-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn reads_json_and_yaml_in_replay_order() {
+        let dir = script_dir("script");
+        let json = r#"{"events": [
+            {"atMs": 500, "type": "session_remove", "sessionId": 1},
+            {"atMs": 0, "type": "session_create", "sessionId": 1, "source": "Mock.exe"}
+        ]}"#;
+        let yaml = "
+repeat: true
+events:
+  - atMs: 500
+    type: session_remove
+    sessionId: 1
+  - atMs: 0
+    type: session_create
+    sessionId: 1
+    source: Mock.exe
+";
+        fs::write(dir.join("script.json"), json).unwrap();
+        fs::write(dir.join("script.yaml"), yaml).unwrap();
+
+        let json = Script::from_file(dir.join("script.json")).unwrap();
+        let yaml = Script::from_file(dir.join("script.yaml")).unwrap();
+        assert!(!json.repeat);
+        assert!(yaml.repeat);
+        for script in [json, yaml] {
+            let at_ms = script.events.iter().map(|e| e.at_ms).collect::<Vec<_>>();
+            assert_eq!(at_ms, vec![0, 500]);
+            assert!(matches!(
+                &script.events[0].event,
+                ScriptEvent::SessionCreate { session_id: 1, source } if source == "Mock.exe"
+            ));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn loads_thumbnails_next_to_the_script() {
+        let dir = script_dir("script-thumbnail");
+        fs::create_dir_all(dir.join("art")).unwrap();
+        fs::write(dir.join("art/cover.jpg"), [1, 2, 3]).unwrap();
+        let script = r#"{"events": [
+            {"atMs": 0, "type": "thumbnail", "sessionId": 1, "path": "art/cover.jpg"}
+        ]}"#;
+        fs::write(dir.join("script.json"), script).unwrap();
+        let missing = script.replace("cover.jpg", "missing.jpg");
+        fs::write(dir.join("missing.json"), missing).unwrap();
+
+        let script = Script::from_file(dir.join("script.json")).unwrap();
+        assert!(matches!(
+            &script.events[0].event,
+            ScriptEvent::Thumbnail { data: Some(data), .. } if data == &[1, 2, 3]
+        ));
+        assert!(Script::from_file(dir.join("missing.json")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reports_a_broken_script_as_a_conversion_failure() {
+        let dir = script_dir("script-broken");
+        fs::write(
+            dir.join("script.json"),
+            r#"{"events": [{"type": "dance"}]}"#,
+        )
+        .unwrap();
+
+        let err = Script::from_file(dir.join("script.json")).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::ConversionFailed));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
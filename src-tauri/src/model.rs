@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+// These mirror the models of gsmtc so that every backend emits the same payloads.
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionModel {
+    pub playback: Option<PlaybackModel>,
+    pub timeline: Option<TimelineModel>,
+    pub media: Option<MediaModel>,
+    pub source: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PlaybackModel {
+    pub status: PlaybackStatus,
+    pub r#type: PlaybackType,
+    pub rate: f64,
+    pub shuffle: bool,
+    pub auto_repeat: AutoRepeatMode,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TimelineModel {
+    pub start: i64,
+    pub end: i64,
+    pub position: i64,
+    pub last_updated_at_ms: i64,
+}
+
+// TimelineModel positions are in 100ns ticks
+pub const TICKS_PER_MILLISECOND: i64 = 10_000;
+
+pub fn ticks_to_ms(ticks: i64) -> i64 {
+    ticks / TICKS_PER_MILLISECOND
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaModel {
+    pub title: String,
+    pub subtitle: String,
+    pub artist: String,
+    pub album: Option<AlbumModel>,
+    pub track_number: Option<u32>,
+    pub genres: Vec<String>,
+    pub playback_type: PlaybackType,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AlbumModel {
+    pub artist: String,
+    pub title: String,
+    pub track_count: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaybackStatus {
+    Closed,
+    Opened,
+    Changing,
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PlaybackType {
+    #[default]
+    Unknown,
+    Music,
+    Video,
+    Image,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AutoRepeatMode {
+    #[default]
+    None,
+    Track,
+    List,
+}
+
+impl AutoRepeatMode {
+    // the order a repeat button steps through
+    pub fn cycle(self) -> Self {
+        match self {
+            AutoRepeatMode::None => AutoRepeatMode::List,
+            AutoRepeatMode::List => AutoRepeatMode::Track,
+            AutoRepeatMode::Track => AutoRepeatMode::None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentSession {
+    pub session_id: usize,
+    pub source: String,
+    pub session: SessionModel,
+    pub art_id: Option<String>,
+    pub palette: Option<Palette>,
+}
+
+// the processed art behind an art_id, fetched separately so that events stay small
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Artwork {
+    pub art_id: String,
+    pub thumbnails: Vec<Thumbnail>,
+    pub palette: Option<Palette>,
+}
+
+// a square rendition of the album art, sized for one of the widget modes
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Thumbnail {
+    pub size: u32,
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+// colors taken from the album art, as #rrggbb
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Palette {
+    pub dominant: String,
+    pub accent: String,
+    // black or white, whichever reads better on the dominant color
+    pub foreground: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionCreate {
+    pub session_id: usize,
+    pub source: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionUpdate {
+    pub session_id: usize,
+    pub source: String,
+    pub session_model: SessionModel,
+    // raw art as the backend delivered it, replaced by an art_id before it reaches the frontend
+    pub image: Option<Vec<u8>>,
+    #[serde(default)]
+    pub art_id: Option<String>,
+    #[serde(default)]
+    pub palette: Option<Palette>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionRemove {
+    pub session_id: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ActiveSessionChange {
+    pub session_id: usize,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ActiveSessionRemove;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub enum SessionControl {
+    Play,
+    Pause,
+    TogglePlayPause,
+    FastForward,
+    Rewind,
+    SkipNext,
+    SkipPrevious,
+    // positions and offsets are in 100ns ticks, like TimelineModel
+    SeekTo(i64),
+    SeekBy(i64),
+    ToggleShuffle,
+    SetShuffle(bool),
+    CycleRepeat,
+    SetRepeat(AutoRepeatMode),
+    SetRate(f64),
+}
+
+// a track playing in a made up player, tests change whatever they are about
+#[cfg(test)]
+pub(crate) fn test_session(title: &str, artist: &str) -> SessionModel {
+    SessionModel {
+        playback: Some(PlaybackModel {
+            status: PlaybackStatus::Playing,
+            r#type: PlaybackType::Music,
+            rate: 1.0,
+            shuffle: false,
+            auto_repeat: AutoRepeatMode::None,
+        }),
+        timeline: None,
+        media: Some(MediaModel {
+            title: title.to_string(),
+            subtitle: String::new(),
+            artist: artist.to_string(),
+            album: None,
+            track_number: None,
+            genres: Vec::new(),
+            playback_type: PlaybackType::Music,
+        }),
+        source: "Spotify.exe".to_string(),
+    }
+}
@@ -0,0 +1,248 @@
+use std::collections::HashMap;
+
+use percent_encoding::percent_decode_str;
+use zbus::zvariant::{Dict, OwnedValue, Value};
+
+use crate::{
+    error::MediaError,
+    model::{
+        AlbumModel, AutoRepeatMode, MediaModel, PlaybackModel, PlaybackStatus, PlaybackType,
+        SessionModel, TimelineModel,
+    },
+    position::now_ms,
+};
+
+// MPRIS reports times in microseconds while the models use 100ns ticks like gsmtc
+pub const TICKS_PER_MICROSECOND: i64 = 10;
+
+pub type PlayerProperties = HashMap<String, OwnedValue>;
+
+// entries of a{sv} maps arrive wrapped in a variant
+fn unwrap_variant<'a>(value: &'a Value<'a>) -> &'a Value<'a> {
+    match value {
+        Value::Value(inner) => unwrap_variant(inner),
+        v => v,
+    }
+}
+
+fn as_string(value: &Value) -> Option<String> {
+    match unwrap_variant(value) {
+        Value::Str(s) => Some(s.to_string()),
+        Value::ObjectPath(p) => Some(p.to_string()),
+        _ => None,
+    }
+}
+
+fn as_string_list(value: &Value) -> Option<Vec<String>> {
+    match unwrap_variant(value) {
+        Value::Array(a) => Some(a.inner().iter().filter_map(as_string).collect()),
+        // some players send a plain string where the spec asks for a list
+        Value::Str(s) => Some(vec![s.to_string()]),
+        _ => None,
+    }
+}
+
+fn as_i64(value: &Value) -> Option<i64> {
+    match unwrap_variant(value) {
+        Value::I64(i) => Some(*i),
+        Value::U64(u) => i64::try_from(*u).ok(),
+        Value::I32(i) => Some(*i as i64),
+        Value::U32(u) => Some(*u as i64),
+        Value::F64(f) => Some(*f as i64),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match unwrap_variant(value) {
+        Value::F64(f) => Some(*f),
+        v => as_i64(v).map(|i| i as f64),
+    }
+}
+
+fn as_bool(value: &Value) -> Option<bool> {
+    match unwrap_variant(value) {
+        Value::Bool(b) => Some(*b),
+        _ => None,
+    }
+}
+
+fn metadata(properties: &PlayerProperties) -> Option<&Dict<'_, '_>> {
+    match unwrap_variant(properties.get("Metadata")?) {
+        Value::Dict(d) => Some(d),
+        _ => None,
+    }
+}
+
+fn metadata_entry<'a>(metadata: &'a Dict<'a, 'a>, key: &str) -> Option<&'a Value<'a>> {
+    metadata
+        .iter()
+        .find(|(k, _)| as_string(k).as_deref() == Some(key))
+        .map(|(_, v)| v)
+}
+
+pub fn convert_playback_info(properties: &PlayerProperties) -> Result<PlaybackModel, MediaError> {
+    let status = match properties.get("PlaybackStatus").and_then(|v| as_string(v)) {
+        Some(s) => match s.as_str() {
+            "Playing" => PlaybackStatus::Playing,
+            "Paused" => PlaybackStatus::Paused,
+            "Stopped" => PlaybackStatus::Stopped,
+            _ => {
+                return Err(MediaError::conversion_failed("Unknown playback status."));
+            }
+        },
+        None => {
+            return Err(MediaError::conversion_failed(
+                "Failed to get playback status.",
+            ));
+        }
+    };
+    let auto_repeat = match properties.get("LoopStatus").and_then(|v| as_string(v)) {
+        Some(s) => match s.as_str() {
+            "None" => Some(AutoRepeatMode::None),
+            "Track" => Some(AutoRepeatMode::Track),
+            "Playlist" => Some(AutoRepeatMode::List),
+            _ => None,
+        },
+        None => None,
+    };
+    let rate = properties.get("Rate").and_then(|v| as_f64(v));
+    let shuffle = properties.get("Shuffle").and_then(|v| as_bool(v));
+    let playback = PlaybackModel {
+        status,
+        // MPRIS does not tell music and video apart
+        r#type: PlaybackType::Unknown,
+        rate: rate.unwrap_or(1.0),
+        shuffle: shuffle.unwrap_or_default(),
+        auto_repeat: auto_repeat.unwrap_or_default(),
+    };
+    Ok(playback)
+}
+
+pub fn loop_status(mode: AutoRepeatMode) -> &'static str {
+    match mode {
+        AutoRepeatMode::None => "None",
+        AutoRepeatMode::Track => "Track",
+        AutoRepeatMode::List => "Playlist",
+    }
+}
+
+pub fn convert_timeline_info(properties: &PlayerProperties) -> Result<TimelineModel, MediaError> {
+    let Some(metadata) = metadata(properties) else {
+        return Err(MediaError::conversion_failed("Failed to get metadata."));
+    };
+    let end = match metadata_entry(metadata, "mpris:length").and_then(as_i64) {
+        Some(l) => l * TICKS_PER_MICROSECOND,
+        None => {
+            return Err(MediaError::conversion_failed("Failed to get track length."));
+        }
+    };
+    let position = match properties.get("Position").and_then(|v| as_i64(v)) {
+        Some(p) => p * TICKS_PER_MICROSECOND,
+        None => {
+            return Err(MediaError::conversion_failed("Failed to get position."));
+        }
+    };
+    // the position is read on demand, so it is as fresh as the current time
+    let timeline = TimelineModel {
+        start: 0,
+        end,
+        position,
+        last_updated_at_ms: now_ms(),
+    };
+    Ok(timeline)
+}
+
+pub fn convert_album_info(properties: &PlayerProperties) -> Result<AlbumModel, MediaError> {
+    let Some(metadata) = metadata(properties) else {
+        return Err(MediaError::conversion_failed("Failed to get metadata."));
+    };
+    let album_title = match metadata_entry(metadata, "xesam:album").and_then(as_string) {
+        Some(a) => a,
+        None => {
+            return Err(MediaError::conversion_failed("Failed to get album title."));
+        }
+    };
+    let album_artist = metadata_entry(metadata, "xesam:albumArtist")
+        .and_then(as_string_list)
+        .unwrap_or_default()
+        .join(", ");
+    let album = AlbumModel {
+        artist: album_artist,
+        title: album_title,
+        // not part of the MPRIS metadata spec
+        track_count: 0,
+    };
+    Ok(album)
+}
+
+pub fn convert_media_info(properties: &PlayerProperties) -> Result<MediaModel, MediaError> {
+    let Some(metadata) = metadata(properties) else {
+        return Err(MediaError::conversion_failed("Failed to get metadata."));
+    };
+    let album = convert_album_info(properties).ok();
+    let title = match metadata_entry(metadata, "xesam:title").and_then(as_string) {
+        Some(t) => t,
+        None => {
+            return Err(MediaError::conversion_failed("Failed to get title."));
+        }
+    };
+    let artist = metadata_entry(metadata, "xesam:artist")
+        .and_then(as_string_list)
+        .unwrap_or_default()
+        .join(", ");
+    let track_number = metadata_entry(metadata, "xesam:trackNumber")
+        .and_then(as_i64)
+        .and_then(|n| u32::try_from(n).ok());
+    let genres = metadata_entry(metadata, "xesam:genre")
+        .and_then(as_string_list)
+        .unwrap_or_default();
+
+    let media = MediaModel {
+        title,
+        subtitle: String::new(),
+        artist,
+        album,
+        track_number,
+        genres,
+        playback_type: PlaybackType::Unknown,
+    };
+    Ok(media)
+}
+
+pub fn convert_session_model(source: &str, properties: &PlayerProperties) -> SessionModel {
+    SessionModel {
+        playback: convert_playback_info(properties).ok(),
+        timeline: convert_timeline_info(properties).ok(),
+        media: convert_media_info(properties).ok(),
+        source: source.to_string(),
+    }
+}
+
+pub fn get_art_url(properties: &PlayerProperties) -> Option<String> {
+    metadata_entry(metadata(properties)?, "mpris:artUrl").and_then(as_string)
+}
+
+pub fn get_track_id(properties: &PlayerProperties) -> Option<String> {
+    metadata_entry(metadata(properties)?, "mpris:trackid").and_then(as_string)
+}
+
+fn decode_file_url(url: &str) -> Option<String> {
+    let path = url.strip_prefix("file://")?;
+    percent_decode_str(path)
+        .decode_utf8()
+        .ok()
+        .map(|p| p.into_owned())
+}
+
+// only local art is supported, remote urls would need an http client
+pub fn load_thumbnail(art_url: &str) -> Option<Vec<u8>> {
+    let path = decode_file_url(art_url)?;
+    match std::fs::read(&path) {
+        Ok(data) => Some(data),
+        Err(err) => {
+            println!("Failed to read thumbnail {path}.\n{}", err);
+            None
+        }
+    }
+}
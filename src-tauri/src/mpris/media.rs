@@ -0,0 +1,531 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex as SyncMutex},
+};
+
+use futures_util::{stream, StreamExt};
+use tauri::async_runtime::{channel, JoinHandle, Receiver, Sender};
+use zbus::{
+    blocking,
+    fdo::{DBusProxy, PropertiesProxy},
+    names::InterfaceName,
+    zvariant::ObjectPath,
+    CacheProperties, Connection,
+};
+
+use crate::{
+    backend::{BackendEvent, MediaBackend},
+    error::MediaError,
+    model::{
+        ActiveSessionChange, PlaybackModel, PlaybackStatus, SessionControl, SessionCreate,
+        SessionRemove, SessionUpdate,
+    },
+};
+
+use super::{
+    convert::{
+        convert_playback_info, convert_session_model, get_art_url, get_track_id, load_thumbnail,
+        loop_status, PlayerProperties, TICKS_PER_MICROSECOND,
+    },
+    proxy::{PlayerProxy, PlayerProxyBlocking, BUS_NAME_PREFIX, OBJECT_PATH, PLAYER_INTERFACE},
+};
+
+// FastForward and Rewind have no MPRIS counterpart, so they seek by this many microseconds
+const SEEK_STEP_US: i64 = 10_000_000;
+
+struct Player {
+    session_id: usize,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct PlayerRegistry {
+    next_id: usize,
+    players: HashMap<String, Player>,
+    active: Option<usize>,
+}
+
+pub struct MprisBackend {
+    pub connection: blocking::Connection,
+    // shared with the event task, which assigns the session ids
+    registry: Arc<SyncMutex<PlayerRegistry>>,
+}
+
+fn source_from_bus_name(bus_name: &str) -> &str {
+    bus_name.strip_prefix(BUS_NAME_PREFIX).unwrap_or(bus_name)
+}
+
+fn player_interface() -> InterfaceName<'static> {
+    InterfaceName::from_static_str_unchecked(PLAYER_INTERFACE)
+}
+
+impl MprisBackend {
+    pub fn new() -> Result<Self, MediaError> {
+        let connection = match blocking::Connection::session() {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(MediaError::backend_unavailable(
+                    "Failed to connect to the session bus.",
+                )
+                .with_source(err));
+            }
+        };
+        Ok(Self {
+            connection,
+            registry: Arc::new(SyncMutex::new(PlayerRegistry::default())),
+        })
+    }
+
+    fn find_bus_name(&self, session_id: usize) -> Option<String> {
+        let registry = self.registry.lock().unwrap();
+        registry
+            .players
+            .iter()
+            .find(|(_, player)| player.session_id == session_id)
+            .map(|(bus_name, _)| bus_name.clone())
+    }
+
+    fn read_properties(&self, bus_name: &str) -> zbus::Result<PlayerProperties> {
+        let proxy = blocking::fdo::PropertiesProxy::builder(&self.connection)
+            .destination(bus_name)?
+            .path(OBJECT_PATH)?
+            .build()?;
+        Ok(proxy.get_all(Some(player_interface()).into())?)
+    }
+
+    // toggles and cycles start from the state the player reports right now
+    fn read_playback(&self, bus_name: &str) -> zbus::Result<PlaybackModel> {
+        let props = self.read_properties(bus_name)?;
+        convert_playback_info(&props).map_err(|err| zbus::Error::Failure(err.to_string()))
+    }
+
+    pub fn control(&self, bus_name: &str, control: &SessionControl) -> zbus::Result<()> {
+        let player = PlayerProxyBlocking::builder(&self.connection)
+            .destination(bus_name)?
+            .cache_properties(CacheProperties::No)
+            .build()?;
+        match control {
+            SessionControl::Play => player.play()?,
+            SessionControl::Pause => player.pause()?,
+            SessionControl::TogglePlayPause => player.play_pause()?,
+            SessionControl::FastForward => player.seek(SEEK_STEP_US)?,
+            SessionControl::Rewind => player.seek(-SEEK_STEP_US)?,
+            SessionControl::SkipNext => player.next()?,
+            SessionControl::SkipPrevious => player.previous()?,
+            SessionControl::SeekTo(position) => {
+                // SetPosition is ignored unless it names the current track
+                let props = self.read_properties(bus_name)?;
+                let Some(track_id) = get_track_id(&props) else {
+                    return Err(zbus::Error::Failure(
+                        "The player has no current track.".to_string(),
+                    ));
+                };
+                let track_id = ObjectPath::try_from(track_id.as_str())?;
+                player.set_position(&track_id, position / TICKS_PER_MICROSECOND)?
+            }
+            SessionControl::SeekBy(offset) => player.seek(offset / TICKS_PER_MICROSECOND)?,
+            SessionControl::ToggleShuffle => {
+                let playback = self.read_playback(bus_name)?;
+                player.set_shuffle(!playback.shuffle)?
+            }
+            SessionControl::SetShuffle(shuffle) => player.set_shuffle(*shuffle)?,
+            SessionControl::CycleRepeat => {
+                let playback = self.read_playback(bus_name)?;
+                player.set_loop_status(loop_status(playback.auto_repeat.cycle()))?
+            }
+            SessionControl::SetRepeat(mode) => player.set_loop_status(loop_status(*mode))?,
+            SessionControl::SetRate(rate) => player.set_rate(*rate)?,
+        };
+        Ok(())
+    }
+
+    async fn forward_events(
+        registry: Arc<SyncMutex<PlayerRegistry>>,
+        tx: Sender<BackendEvent>,
+    ) -> zbus::Result<()> {
+        let connection = Connection::session().await?;
+        let dbus = DBusProxy::new(&connection).await?;
+
+        // subscribe before listing so that no player slips through in between
+        let mut name_owner_changed = dbus.receive_name_owner_changed().await?;
+        for name in dbus.list_names().await? {
+            if name.starts_with(BUS_NAME_PREFIX) {
+                MprisBackend::add_player(&connection, &registry, &tx, name.to_string()).await;
+            }
+        }
+
+        while let Some(signal) = name_owner_changed.next().await {
+            let Ok(args) = signal.args() else {
+                continue;
+            };
+            let bus_name = args.name().to_string();
+            if !bus_name.starts_with(BUS_NAME_PREFIX) {
+                continue;
+            }
+            if args.new_owner().is_some() {
+                MprisBackend::add_player(&connection, &registry, &tx, bus_name).await;
+            } else {
+                MprisBackend::remove_player(&registry, &tx, &bus_name).await;
+            }
+            if tx.is_closed() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_player(
+        connection: &Connection,
+        registry: &Arc<SyncMutex<PlayerRegistry>>,
+        tx: &Sender<BackendEvent>,
+        bus_name: String,
+    ) {
+        let source = source_from_bus_name(&bus_name).to_string();
+        let session_id = {
+            let mut registry = registry.lock().unwrap();
+            if registry.players.contains_key(&bus_name) {
+                return;
+            }
+            let session_id = registry.next_id;
+            registry.next_id += 1;
+            session_id
+        };
+        // announce the session before its watcher can send the first update
+        tx.send(BackendEvent::SessionCreate(SessionCreate {
+            session_id,
+            source,
+        }))
+        .await
+        .ok();
+        let task = tauri::async_runtime::spawn(MprisBackend::watch_player(
+            connection.clone(),
+            Arc::clone(registry),
+            tx.clone(),
+            bus_name.clone(),
+            session_id,
+        ));
+        registry
+            .lock()
+            .unwrap()
+            .players
+            .insert(bus_name, Player { session_id, task });
+    }
+
+    async fn remove_player(
+        registry: &Arc<SyncMutex<PlayerRegistry>>,
+        tx: &Sender<BackendEvent>,
+        bus_name: &str,
+    ) {
+        let (session_id, active_change) = {
+            let mut registry = registry.lock().unwrap();
+            let Some(player) = registry.players.remove(bus_name) else {
+                return;
+            };
+            player.task.abort();
+            // MPRIS has no notion of a current session, so fall back to any remaining player
+            let active_change = if registry.active == Some(player.session_id) {
+                registry.active = registry.players.values().map(|p| p.session_id).min();
+                Some(registry.active)
+            } else {
+                None
+            };
+            (player.session_id, active_change)
+        };
+        tx.send(BackendEvent::SessionRemove(SessionRemove { session_id }))
+            .await
+            .ok();
+        if let Some(active) = active_change {
+            tx.send(BackendEvent::ActiveSessionChange(
+                active.map(|id| ActiveSessionChange { session_id: id }),
+            ))
+            .await
+            .ok();
+        }
+    }
+
+    async fn watch_player(
+        connection: Connection,
+        registry: Arc<SyncMutex<PlayerRegistry>>,
+        tx: Sender<BackendEvent>,
+        bus_name: String,
+        session_id: usize,
+    ) {
+        let source = source_from_bus_name(&bus_name).to_string();
+        if let Err(err) =
+            MprisBackend::watch_player_inner(&connection, &registry, &tx, &bus_name, session_id)
+                .await
+        {
+            println!("[{session_id}/{source}] failed to watch player.\n{}", err);
+        }
+        println!("[{session_id}/{source}] exited event-loop");
+    }
+
+    async fn watch_player_inner(
+        connection: &Connection,
+        registry: &Arc<SyncMutex<PlayerRegistry>>,
+        tx: &Sender<BackendEvent>,
+        bus_name: &str,
+        session_id: usize,
+    ) -> zbus::Result<()> {
+        let source = source_from_bus_name(bus_name).to_string();
+        let properties = PropertiesProxy::builder(connection)
+            .destination(bus_name.to_string())?
+            .path(OBJECT_PATH)?
+            .build()
+            .await?;
+        let player = PlayerProxy::builder(connection)
+            .destination(bus_name.to_string())?
+            .build()
+            .await?;
+
+        // the position is not covered by PropertiesChanged, so seeks are watched separately
+        let properties_changed = properties.receive_properties_changed().await?.map(|_| ());
+        let seeked = player.receive_seeked().await?.map(|_| ());
+        let mut changes = stream::select(properties_changed, seeked);
+
+        let mut last_art_url: Option<String> = None;
+        loop {
+            let props = properties.get_all(Some(player_interface()).into()).await?;
+            let session_model = convert_session_model(&source, &props);
+
+            let art_url = get_art_url(&props);
+            let image = if art_url != last_art_url {
+                last_art_url = art_url.clone();
+                art_url.as_deref().and_then(load_thumbnail)
+            } else {
+                None
+            };
+
+            let is_playing = session_model
+                .playback
+                .as_ref()
+                .is_some_and(|p| p.status == PlaybackStatus::Playing);
+            let became_active = {
+                let mut registry = registry.lock().unwrap();
+                if (is_playing || registry.active.is_none()) && registry.active != Some(session_id)
+                {
+                    registry.active = Some(session_id);
+                    true
+                } else {
+                    false
+                }
+            };
+
+            let update = SessionUpdate {
+                session_id,
+                source: source.clone(),
+                session_model,
+                image,
+                art_id: None,
+                palette: None,
+            };
+            if tx.send(BackendEvent::SessionUpdate(update)).await.is_err() {
+                break;
+            }
+            if became_active {
+                tx.send(BackendEvent::ActiveSessionChange(Some(
+                    ActiveSessionChange { session_id },
+                )))
+                .await
+                .ok();
+            }
+
+            if changes.next().await.is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MediaBackend for MprisBackend {
+    fn control_session(
+        &self,
+        session_id: usize,
+        control: SessionControl,
+    ) -> Result<(), MediaError> {
+        let Some(bus_name) = self.find_bus_name(session_id) else {
+            return Err(
+                MediaError::not_found(format!("Session {session_id} no longer exists."))
+                    .with_session(session_id),
+            );
+        };
+        match self.control(&bus_name, &control) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(MediaError::control_rejected(format!(
+                "Failed to control {} session: {:?}",
+                source_from_bus_name(&bus_name),
+                control
+            ))
+            .with_session(session_id)
+            .with_source(err)),
+        }
+    }
+
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        let (tx, rx) = channel(64);
+        let registry = Arc::clone(&self.registry);
+        tauri::async_runtime::spawn(async move {
+            if let Err(err) = MprisBackend::forward_events(registry, tx).await {
+                println!("Failed to watch MPRIS players.\n{}", err);
+            }
+        });
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        time::Duration,
+    };
+
+    use zbus::{
+        interface,
+        object_server::SignalContext,
+        zvariant::{OwnedValue, Value},
+    };
+
+    use super::*;
+
+    const FAKE_BUS_NAME: &str = "org.mpris.MediaPlayer2.fake";
+
+    // a bus of its own, so that the players of the machine running the tests stay out of it
+    struct PrivateBus(Child);
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.0.kill();
+            let _ = self.0.wait();
+        }
+    }
+
+    fn start_bus() -> Option<(PrivateBus, String)> {
+        let child = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        let mut bus = PrivateBus(child);
+        let mut address = String::new();
+        BufReader::new(bus.0.stdout.take()?)
+            .read_line(&mut address)
+            .ok()?;
+        Some((bus, address.trim().to_string()))
+    }
+
+    struct FakePlayer {
+        playing: bool,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl FakePlayer {
+        async fn play(
+            &mut self,
+            #[zbus(signal_context)] ctxt: SignalContext<'_>,
+        ) -> zbus::fdo::Result<()> {
+            self.playing = true;
+            self.playback_status_changed(&ctxt).await?;
+            Ok(())
+        }
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            let status = if self.playing { "Playing" } else { "Paused" };
+            status.to_string()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            let value = |v: Value| OwnedValue::try_from(v).unwrap();
+            HashMap::from([
+                ("xesam:title".to_string(), value(Value::from("Song"))),
+                ("xesam:artist".to_string(), value(Value::from(vec!["Band"]))),
+                (
+                    "mpris:length".to_string(),
+                    value(Value::from(180_000_000i64)),
+                ),
+            ])
+        }
+
+        #[zbus(property)]
+        fn position(&self) -> i64 {
+            5_000_000
+        }
+    }
+
+    fn next_event(rx: &mut Receiver<BackendEvent>) -> BackendEvent {
+        let event = tauri::async_runtime::block_on(async {
+            tokio::time::timeout(Duration::from_secs(5), rx.recv()).await
+        });
+        match event {
+            Ok(Some(event)) => event,
+            _ => panic!("the backend sent no event"),
+        }
+    }
+
+    fn next_update(rx: &mut Receiver<BackendEvent>) -> SessionUpdate {
+        match next_event(rx) {
+            BackendEvent::SessionUpdate(update) => update,
+            event => panic!("expected an update, got {event:?}"),
+        }
+    }
+
+    #[test]
+    fn follows_a_player_on_the_session_bus() {
+        let Some((_bus, address)) = start_bus() else {
+            println!("dbus-daemon is not available, skipping");
+            return;
+        };
+        // both the player and the backend connect to the session bus named here
+        std::env::set_var("DBUS_SESSION_BUS_ADDRESS", &address);
+        let player = blocking::connection::Builder::session()
+            .and_then(|b| b.name(FAKE_BUS_NAME))
+            .and_then(|b| b.serve_at(OBJECT_PATH, FakePlayer { playing: false }))
+            .and_then(|b| b.build())
+            .unwrap();
+
+        let backend = MprisBackend::new().unwrap();
+        let mut rx = backend.subscribe().unwrap();
+        match next_event(&mut rx) {
+            BackendEvent::SessionCreate(create) => {
+                assert_eq!((create.session_id, create.source.as_str()), (0, "fake"));
+            }
+            event => panic!("expected the session to be created, got {event:?}"),
+        }
+        let model = next_update(&mut rx).session_model;
+        assert_eq!(model.media.map(|m| m.title).as_deref(), Some("Song"));
+        assert_eq!(
+            model.playback.map(|p| p.status),
+            Some(PlaybackStatus::Paused)
+        );
+        let timeline = model.timeline.unwrap();
+        assert_eq!(timeline.end, 180_000_000 * TICKS_PER_MICROSECOND);
+        assert_eq!(timeline.position, 5_000_000 * TICKS_PER_MICROSECOND);
+        // the only player is the active one, even while paused
+        assert!(matches!(
+            next_event(&mut rx),
+            BackendEvent::ActiveSessionChange(Some(ActiveSessionChange { session_id: 0 }))
+        ));
+
+        backend.control_session(0, SessionControl::Play).unwrap();
+        let model = next_update(&mut rx).session_model;
+        assert_eq!(
+            model.playback.map(|p| p.status),
+            Some(PlaybackStatus::Playing)
+        );
+
+        player.release_name(FAKE_BUS_NAME).unwrap();
+        assert!(matches!(
+            next_event(&mut rx),
+            BackendEvent::SessionRemove(SessionRemove { session_id: 0 })
+        ));
+        assert!(matches!(
+            next_event(&mut rx),
+            BackendEvent::ActiveSessionChange(None)
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod convert;
+pub mod media;
+pub mod proxy;
@@ -0,0 +1,196 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+use image::ImageFormat;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    artwork::ArtworkCache,
+    error::MediaError,
+    model::{CurrentSession, PlaybackStatus},
+    position::SessionPosition,
+    template::{Template, TemplateContext},
+};
+
+pub const DEFAULT_OUTPUT_FILE_NAME: &str = "now_playing.txt";
+pub const DEFAULT_OUTPUT_TEMPLATE: &str = "{artist} - {title}{if album} [{album}]{end}";
+// the art of the current track, removed while there is none
+pub const COVER_FILE_NAME: &str = "cover.png";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputFile {
+    // a file name inside the output directory
+    pub name: String,
+    pub template: String,
+}
+
+// the [output] table of the settings file, nothing is written without it
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputConfig {
+    pub dir: PathBuf,
+    #[serde(default = "default_files")]
+    pub files: Vec<OutputFile>,
+    // written instead of the templates while nothing is playing, None empties the files
+    #[serde(default)]
+    pub placeholder: Option<String>,
+    // treats a paused track like no track at all
+    #[serde(default)]
+    pub idle_when_paused: bool,
+}
+
+fn default_files() -> Vec<OutputFile> {
+    vec![OutputFile {
+        name: DEFAULT_OUTPUT_FILE_NAME.to_string(),
+        template: DEFAULT_OUTPUT_TEMPLATE.to_string(),
+    }]
+}
+
+impl OutputConfig {
+    // every problem is reported before anything is written
+    pub fn validate(&self) -> Result<Vec<(String, Template)>, MediaError> {
+        let mut files = Vec::with_capacity(self.files.len());
+        for file in self.files.iter() {
+            // plain names only, so that every file ends up in the output directory
+            let is_plain = Path::new(&file.name).file_name() == Some(file.name.as_ref());
+            if !is_plain || file.name == COVER_FILE_NAME {
+                return Err(MediaError::conversion_failed(format!(
+                    "Invalid output file name {:?}.",
+                    file.name
+                )));
+            }
+            files.push((file.name.clone(), Template::parse(&file.template)?));
+        }
+        Ok(files)
+    }
+}
+
+// the files are only written when what they would contain changes
+pub struct FileOutput {
+    dir: PathBuf,
+    files: Vec<(String, Template)>,
+    placeholder: Option<String>,
+    idle_when_paused: bool,
+    written: BTreeMap<String, String>,
+    // None until the cover was first written or removed, Some(None) while there is no cover
+    cover: Option<Option<String>>,
+}
+
+impl FileOutput {
+    pub fn new(config: OutputConfig) -> Result<Self, MediaError> {
+        let files = config.validate()?;
+        if let Err(err) = fs::create_dir_all(&config.dir) {
+            return Err(MediaError::storage_failed(format!(
+                "Failed to create output directory {}.",
+                config.dir.display()
+            ))
+            .with_source(err));
+        }
+        Ok(Self {
+            dir: config.dir,
+            files,
+            placeholder: config.placeholder,
+            idle_when_paused: config.idle_when_paused,
+            written: BTreeMap::new(),
+            cover: None,
+        })
+    }
+
+    // follows the active session, called after the store has applied an event
+    pub fn apply(
+        &mut self,
+        active: Option<&CurrentSession>,
+        position: Option<&SessionPosition>,
+        artwork: &ArtworkCache,
+    ) {
+        let current = active.filter(|s| {
+            let has_media = s
+                .session
+                .media
+                .as_ref()
+                .is_some_and(|m| !m.title.is_empty());
+            let paused = s
+                .session
+                .playback
+                .as_ref()
+                .is_some_and(|p| p.status != PlaybackStatus::Playing);
+            has_media && !(self.idle_when_paused && paused)
+        });
+
+        let ctx = current.map(|s| TemplateContext::new(&s.session, position));
+        for (name, template) in self.files.iter() {
+            let content = match ctx.as_ref() {
+                Some(ctx) => template.render(ctx),
+                None => self.placeholder.clone().unwrap_or_default(),
+            };
+            if self.written.get(name) == Some(&content) {
+                continue;
+            }
+            match write_atomic(&self.dir.join(name), content.as_bytes()) {
+                Ok(()) => {
+                    self.written.insert(name.clone(), content);
+                }
+                Err(err) => println!("[output] failed to write {}: {}", name, err),
+            }
+        }
+
+        let art_id = current.and_then(|s| s.art_id.clone());
+        if self.cover.as_ref() != Some(&art_id) {
+            match self.write_cover(art_id.as_deref(), artwork) {
+                Ok(()) => self.cover = Some(art_id),
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+
+    fn write_cover(&self, art_id: Option<&str>, artwork: &ArtworkCache) -> Result<(), MediaError> {
+        let path = self.dir.join(COVER_FILE_NAME);
+        let largest = art_id
+            .and_then(|id| artwork.get(id))
+            .and_then(|a| a.thumbnails.iter().max_by_key(|t| t.size).cloned());
+        let Some(thumbnail) = largest else {
+            return match fs::remove_file(&path) {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => {
+                    Err(MediaError::storage_failed("Failed to remove the cover.").with_source(err))
+                }
+            };
+        };
+        // thumbnails are mostly jpeg, the file is always png so that its name never changes
+        let data = if thumbnail.mime_type == ImageFormat::Png.to_mime_type() {
+            thumbnail.data
+        } else {
+            let mut data = Vec::new();
+            let result = image::load_from_memory(&thumbnail.data)
+                .and_then(|i| i.write_to(&mut Cursor::new(&mut data), ImageFormat::Png));
+            if let Err(err) = result {
+                return Err(
+                    MediaError::thumbnail_failed("Failed to convert the cover to png.")
+                        .with_source(err),
+                );
+            }
+            data
+        };
+        match write_atomic(&path, &data) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                Err(MediaError::storage_failed("Failed to write the cover.").with_source(err))
+            }
+        }
+    }
+}
+
+// written next to the target and renamed over it, so readers never see half a file
+pub(crate) fn write_atomic(path: &Path, data: &[u8]) -> std::io::Result<()> {
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp = path.with_file_name(tmp_name);
+    fs::write(&tmp, data)?;
+    fs::rename(&tmp, path)
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use image::{imageops::FilterType, DynamicImage};
+
+use crate::model::Palette;
+
+// the art is sampled at this size, which is plenty for a handful of colors
+const SAMPLE_SIZE: u32 = 32;
+// 4 bits per channel, so that near-identical shades end up in the same bucket
+const BUCKET_SHIFT: u8 = 4;
+// accents closer than this to the dominant color would not stand out
+const MIN_ACCENT_DISTANCE: f64 = 80.0;
+
+#[derive(Default)]
+struct Bucket {
+    count: u32,
+    sum: [u32; 3],
+}
+
+impl Bucket {
+    fn average(&self) -> [u8; 3] {
+        let n = self.count.max(1);
+        [
+            (self.sum[0] / n) as u8,
+            (self.sum[1] / n) as u8,
+            (self.sum[2] / n) as u8,
+        ]
+    }
+}
+
+fn saturation([r, g, b]: [u8; 3]) -> f64 {
+    let max = r.max(g).max(b) as f64;
+    let min = r.min(g).min(b) as f64;
+    if max == 0.0 {
+        0.0
+    } else {
+        (max - min) / max
+    }
+}
+
+fn distance(a: [u8; 3], b: [u8; 3]) -> f64 {
+    a.iter()
+        .zip(b.iter())
+        .map(|(&x, &y)| (x as f64 - y as f64).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+// relative luminance as defined by WCAG 2
+fn luminance(color: [u8; 3]) -> f64 {
+    let channel = |c: u8| {
+        let c = c as f64 / 255.0;
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * channel(color[0]) + 0.7152 * channel(color[1]) + 0.0722 * channel(color[2])
+}
+
+fn contrast(a: [u8; 3], b: [u8; 3]) -> f64 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+fn to_hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+pub fn extract_palette(image: &DynamicImage) -> Option<Palette> {
+    let sample = image
+        .resize(SAMPLE_SIZE, SAMPLE_SIZE, FilterType::Triangle)
+        .to_rgba8();
+    // ordered by color, so that ties between equally common colors always go the same way
+    let mut buckets = BTreeMap::<[u8; 3], Bucket>::new();
+    for pixel in sample.pixels() {
+        let [r, g, b, a] = pixel.0;
+        // letterbox bars and transparent art should not count towards the colors
+        if a < 128 {
+            continue;
+        }
+        let key = [r >> BUCKET_SHIFT, g >> BUCKET_SHIFT, b >> BUCKET_SHIFT];
+        let bucket = buckets.entry(key).or_default();
+        bucket.count += 1;
+        bucket.sum[0] += r as u32;
+        bucket.sum[1] += g as u32;
+        bucket.sum[2] += b as u32;
+    }
+
+    let dominant = buckets.values().max_by_key(|b| b.count)?.average();
+    let accent = buckets
+        .values()
+        .map(|b| (b.average(), b.count))
+        .filter(|(color, _)| distance(*color, dominant) >= MIN_ACCENT_DISTANCE)
+        .max_by(|(a, a_count), (b, b_count)| {
+            let score = |color: &[u8; 3], count: &u32| *count as f64 * (0.1 + saturation(*color));
+            score(a, a_count).total_cmp(&score(b, b_count))
+        })
+        .map(|(color, _)| color)
+        .unwrap_or(dominant);
+    let foreground = if contrast([255, 255, 255], dominant) >= contrast([0, 0, 0], dominant) {
+        [255, 255, 255]
+    } else {
+        [0, 0, 0]
+    };
+
+    Some(Palette {
+        dominant: to_hex(dominant),
+        accent: to_hex(accent),
+        foreground: to_hex(foreground),
+    })
+}
@@ -0,0 +1,179 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::{PlaybackStatus, SessionModel, TimelineModel, TICKS_PER_MILLISECOND};
+
+pub fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SessionPosition {
+    pub session_id: usize,
+    pub position: i64,
+    pub start: i64,
+    pub end: i64,
+    pub rate: f64,
+    pub playing: bool,
+}
+
+// extrapolates the position between the timeline updates a backend sends
+#[derive(Debug, Clone)]
+pub struct PlaybackClock {
+    timeline: TimelineModel,
+    // position at anchor_ms
+    position: i64,
+    anchor_ms: i64,
+    rate: f64,
+    playing: bool,
+}
+
+impl PlaybackClock {
+    pub fn update(
+        previous: Option<&PlaybackClock>,
+        model: &SessionModel,
+        now_ms: i64,
+    ) -> Option<PlaybackClock> {
+        let timeline = model.timeline.as_ref()?;
+        let (playing, rate) = match model.playback.as_ref() {
+            Some(p) => (p.status == PlaybackStatus::Playing, p.rate),
+            None => (false, 1.0),
+        };
+        let rate = if rate.is_finite() && rate > 0.0 {
+            rate
+        } else {
+            1.0
+        };
+        // backends only report a new timeline when the position jumps, so a pause or a rate
+        // change rebases the clock instead of going back to the last reported position. a
+        // timestamp from the future can not be trusted, so the clock starts at arrival then
+        let reported_ms = timeline.last_updated_at_ms;
+        let (position, anchor_ms) = match previous {
+            Some(prev) if prev.timeline == *timeline => (prev.position_at(now_ms), now_ms),
+            _ if reported_ms > 0 && reported_ms <= now_ms => (timeline.position, reported_ms),
+            _ => (timeline.position, now_ms),
+        };
+        Some(PlaybackClock {
+            timeline: timeline.clone(),
+            position,
+            anchor_ms,
+            rate,
+            playing,
+        })
+    }
+
+    pub fn position_at(&self, now_ms: i64) -> i64 {
+        let mut position = self.position;
+        if self.playing {
+            let elapsed_ms = (now_ms - self.anchor_ms).max(0) as f64;
+            position += (elapsed_ms * TICKS_PER_MILLISECOND as f64 * self.rate) as i64;
+        }
+        // some players report an empty timeline for live streams
+        if self.timeline.end > self.timeline.start {
+            position = position.min(self.timeline.end).max(self.timeline.start);
+        }
+        position
+    }
+
+    pub fn snapshot(&self, session_id: usize, now_ms: i64) -> SessionPosition {
+        SessionPosition {
+            session_id,
+            position: self.position_at(now_ms),
+            start: self.timeline.start,
+            end: self.timeline.end,
+            rate: self.rate,
+            playing: self.playing,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::test_session;
+
+    const TICKS: i64 = TICKS_PER_MILLISECOND;
+    const NOW_MS: i64 = 1_000_000;
+
+    fn model(
+        status: PlaybackStatus,
+        rate: f64,
+        position_ms: i64,
+        updated_at_ms: i64,
+    ) -> SessionModel {
+        let mut model = test_session("Song", "Band");
+        let playback = model.playback.as_mut().unwrap();
+        playback.status = status;
+        playback.rate = rate;
+        model.timeline = Some(TimelineModel {
+            start: 0,
+            end: 60_000 * TICKS,
+            position: position_ms * TICKS,
+            last_updated_at_ms: updated_at_ms,
+        });
+        model
+    }
+
+    fn clock(model: &SessionModel) -> PlaybackClock {
+        PlaybackClock::update(None, model, NOW_MS).unwrap()
+    }
+
+    #[test]
+    fn extrapolates_by_rate_while_playing() {
+        let playing = clock(&model(PlaybackStatus::Playing, 1.0, 10_000, NOW_MS));
+        assert_eq!(playing.position_at(NOW_MS + 5_000), 15_000 * TICKS);
+
+        let fast = clock(&model(PlaybackStatus::Playing, 2.0, 10_000, NOW_MS));
+        assert_eq!(fast.position_at(NOW_MS + 5_000), 20_000 * TICKS);
+
+        let paused = clock(&model(PlaybackStatus::Paused, 1.0, 10_000, NOW_MS));
+        assert_eq!(paused.position_at(NOW_MS + 5_000), 10_000 * TICKS);
+    }
+
+    #[test]
+    fn extrapolates_from_the_reported_time() {
+        let recent = clock(&model(PlaybackStatus::Playing, 1.0, 10_000, NOW_MS - 1_000));
+        assert_eq!(recent.position_at(NOW_MS), 11_000 * TICKS);
+
+        // a session first seen mid-track is where the player says it is, however old the update
+        let late = clock(&model(
+            PlaybackStatus::Playing,
+            1.0,
+            10_000,
+            NOW_MS - 30_000,
+        ));
+        assert_eq!(late.position_at(NOW_MS), 40_000 * TICKS);
+    }
+
+    #[test]
+    fn starts_at_arrival_without_a_usable_time() {
+        let unknown = clock(&model(PlaybackStatus::Playing, 1.0, 10_000, 0));
+        assert_eq!(unknown.position_at(NOW_MS + 1_000), 11_000 * TICKS);
+
+        let future = clock(&model(PlaybackStatus::Playing, 1.0, 10_000, NOW_MS + 5_000));
+        assert_eq!(future.position_at(NOW_MS + 1_000), 11_000 * TICKS);
+    }
+
+    #[test]
+    fn clamps_to_the_timeline() {
+        let clock = clock(&model(PlaybackStatus::Playing, 1.0, 55_000, NOW_MS));
+        assert_eq!(clock.position_at(NOW_MS + 30_000), 60_000 * TICKS);
+        // an earlier time does not run the clock backwards
+        assert_eq!(clock.position_at(NOW_MS - 30_000), 55_000 * TICKS);
+    }
+
+    #[test]
+    fn rebases_on_a_pause_without_a_new_timeline() {
+        let playing = model(PlaybackStatus::Playing, 1.0, 10_000, NOW_MS);
+        let clock = clock(&playing);
+        let mut paused = playing.clone();
+        paused.playback.as_mut().unwrap().status = PlaybackStatus::Paused;
+        let paused = PlaybackClock::update(Some(&clock), &paused, NOW_MS + 5_000).unwrap();
+        assert_eq!(paused.position_at(NOW_MS + 20_000), 15_000 * TICKS);
+    }
+}
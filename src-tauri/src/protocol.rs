@@ -0,0 +1,102 @@
+use percent_encoding::percent_decode_str;
+use tauri::http::{header, Request, Response, StatusCode};
+
+use crate::{artwork::ArtworkCache, model::Thumbnail};
+
+// art is served as nowplaying://localhost/<art_id>/<size>, or http://nowplaying.localhost/... on windows
+pub const ART_PROTOCOL: &str = "nowplaying";
+
+// art ids are content hashes, so the thumbnail of one at a given size never changes
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+// a request for a size that was not rendered gets another one, which a change to the thumbnail
+// sizes can replace with a closer one, so it is checked again every time
+const FALLBACK_CACHE_CONTROL: &str = "no-cache";
+
+fn empty(status: StatusCode) -> Response<Vec<u8>> {
+    Response::builder()
+        .status(status)
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .body(Vec::new())
+        .unwrap()
+}
+
+// the closest thumbnail that is at least as large, falling back to the largest one
+pub fn pick_thumbnail(thumbnails: &[Thumbnail], size: Option<u32>) -> Option<&Thumbnail> {
+    let largest = thumbnails.iter().max_by_key(|t| t.size);
+    let Some(size) = size else {
+        return largest;
+    };
+    thumbnails
+        .iter()
+        .filter(|t| t.size >= size)
+        .min_by_key(|t| t.size)
+        .or(largest)
+}
+
+// the cache is only managed once setup ran, requests before that are answered with 503
+pub fn artwork_response(
+    cache: Option<&ArtworkCache>,
+    request: &Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let Some(cache) = cache else {
+        return empty(StatusCode::SERVICE_UNAVAILABLE);
+    };
+    if request.method() != "GET" && request.method() != "HEAD" {
+        return empty(StatusCode::METHOD_NOT_ALLOWED);
+    }
+    // convertFileSrc encodes the whole path, slashes included
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let mut segments = path.trim_matches('/').split('/');
+    let art_id = segments.next().unwrap_or_default();
+    let size = match segments.next() {
+        Some(s) => match s.parse::<u32>() {
+            Ok(s) => Some(s),
+            Err(_) => return empty(StatusCode::BAD_REQUEST),
+        },
+        None => None,
+    };
+    if segments.next().is_some() {
+        return empty(StatusCode::NOT_FOUND);
+    }
+
+    let Some(artwork) = cache.get(art_id) else {
+        return empty(StatusCode::NOT_FOUND);
+    };
+    let Some(thumbnail) = pick_thumbnail(&artwork.thumbnails, size) else {
+        return empty(StatusCode::NOT_FOUND);
+    };
+
+    let etag = format!("\"{}-{}\"", artwork.art_id, thumbnail.size);
+    let cache_control = if size == Some(thumbnail.size) {
+        CACHE_CONTROL
+    } else {
+        FALLBACK_CACHE_CONTROL
+    };
+    let builder = Response::builder()
+        .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, "*")
+        .header(header::CACHE_CONTROL, cache_control)
+        .header(header::ETAG, &etag);
+    let not_modified = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.trim() == etag));
+    if not_modified {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .unwrap();
+    }
+
+    let body = if request.method() == "HEAD" {
+        Vec::new()
+    } else {
+        thumbnail.data.clone()
+    };
+    builder
+        .status(StatusCode::OK)
+        .header(header::CONTENT_TYPE, &thumbnail.mime_type)
+        .header(header::CONTENT_LENGTH, thumbnail.data.len())
+        .body(body)
+        .unwrap()
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use md5::{Digest, Md5};
+use serde::{Deserialize, Serialize};
+
+use super::{agent, ScrobbleClient, ScrobbleService, ScrobbleTrack, SubmitError};
+
+pub const LASTFM_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+
+// error codes worth trying again: operation failed, offline, unavailable, rate limited, and the
+// authentication errors, which go away once the credentials are fixed
+const RETRY_ERROR_CODES: [i64; 9] = [4, 8, 9, 10, 11, 14, 16, 26, 29];
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct LastFmConfig {
+    pub api_key: String,
+    pub api_secret: String,
+    // from the desktop or mobile auth flow, valid until the user revokes it
+    pub session_key: String,
+    // points the client at another server, e.g. a local mock
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+}
+
+fn default_api_url() -> String {
+    LASTFM_API_URL.to_string()
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error: i64,
+    message: String,
+}
+
+pub struct LastFmClient {
+    config: LastFmConfig,
+    agent: ureq::Agent,
+}
+
+impl LastFmClient {
+    pub fn new(config: LastFmConfig) -> Self {
+        Self {
+            config,
+            agent: agent(),
+        }
+    }
+
+    // md5 of every parameter name and value in name order, followed by the secret
+    fn sign(&self, params: &BTreeMap<&str, String>) -> String {
+        let mut hasher = Md5::new();
+        for (name, value) in params.iter() {
+            hasher.update(name.as_bytes());
+            hasher.update(value.as_bytes());
+        }
+        hasher.update(self.config.api_secret.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+
+    fn call(
+        &self,
+        method: &str,
+        track: &ScrobbleTrack,
+        extra: &[(&'static str, String)],
+    ) -> Result<(), SubmitError> {
+        let mut params = BTreeMap::<&str, String>::new();
+        params.insert("method", method.to_string());
+        params.insert("api_key", self.config.api_key.clone());
+        params.insert("sk", self.config.session_key.clone());
+        params.insert("artist", track.artist.clone());
+        params.insert("track", track.title.clone());
+        if let Some(album) = track.album.as_ref() {
+            params.insert("album", album.clone());
+        }
+        if let Some(duration_ms) = track.duration_ms {
+            params.insert("duration", (duration_ms / 1000).to_string());
+        }
+        params.extend(extra.iter().cloned());
+        let signature = self.sign(&params);
+        params.insert("api_sig", signature);
+        // format is not part of the signature
+        params.insert("format", "json".to_string());
+
+        let form = params
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect::<Vec<_>>();
+        let (status, body) = match self.agent.post(&self.config.api_url).send_form(&form) {
+            Ok(r) => (r.status(), r.into_string().unwrap_or_default()),
+            Err(ureq::Error::Status(status, r)) => (status, r.into_string().unwrap_or_default()),
+            Err(err) => return Err(SubmitError::Retry(err.to_string())),
+        };
+        // errors can come with any status, including 200
+        if let Ok(err) = serde_json::from_str::<ApiError>(&body) {
+            let message = format!("{} ({})", err.message, err.error);
+            return if RETRY_ERROR_CODES.contains(&err.error) {
+                Err(SubmitError::Retry(message))
+            } else {
+                Err(SubmitError::Drop(message))
+            };
+        }
+        match status {
+            200..=299 => Ok(()),
+            429 | 500.. => Err(SubmitError::Retry(format!("HTTP {status}"))),
+            _ => Err(SubmitError::Drop(format!("HTTP {status}"))),
+        }
+    }
+}
+
+impl ScrobbleClient for LastFmClient {
+    fn service(&self) -> ScrobbleService {
+        ScrobbleService::LastFm
+    }
+
+    fn now_playing(&self, track: &ScrobbleTrack) -> Result<(), SubmitError> {
+        self.call("track.updateNowPlaying", track, &[])
+    }
+
+    fn scrobble(&self, track: &ScrobbleTrack, listened_at: i64) -> Result<(), SubmitError> {
+        self.call(
+            "track.scrobble",
+            track,
+            &[("timestamp", listened_at.to_string())],
+        )
+    }
+}
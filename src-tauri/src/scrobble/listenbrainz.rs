@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{agent, ScrobbleClient, ScrobbleService, ScrobbleTrack, SubmitError};
+
+pub const LISTENBRAINZ_API_URL: &str = "https://api.listenbrainz.org";
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ListenBrainzConfig {
+    // the user token from the listenbrainz settings page
+    pub token: String,
+    // points the client at another server, e.g. a local mock
+    #[serde(default = "default_api_url")]
+    pub api_url: String,
+}
+
+fn default_api_url() -> String {
+    LISTENBRAINZ_API_URL.to_string()
+}
+
+pub struct ListenBrainzClient {
+    config: ListenBrainzConfig,
+    agent: ureq::Agent,
+}
+
+impl ListenBrainzClient {
+    pub fn new(config: ListenBrainzConfig) -> Self {
+        Self {
+            config,
+            agent: agent(),
+        }
+    }
+
+    fn submit(&self, listen_type: &str, listen: Value) -> Result<(), SubmitError> {
+        let url = format!(
+            "{}/1/submit-listens",
+            self.config.api_url.trim_end_matches('/')
+        );
+        let body = json!({
+            "listen_type": listen_type,
+            "payload": [listen],
+        });
+        let result = self
+            .agent
+            .post(&url)
+            .set("Authorization", &format!("Token {}", self.config.token))
+            .send_json(body);
+        match result {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let message = format!(
+                    "HTTP {status}: {}",
+                    response.into_string().unwrap_or_default()
+                );
+                // 401 means a bad token, which the user can still fix
+                if status == 401 || status == 429 || status >= 500 {
+                    Err(SubmitError::Retry(message))
+                } else {
+                    Err(SubmitError::Drop(message))
+                }
+            }
+            Err(err) => Err(SubmitError::Retry(err.to_string())),
+        }
+    }
+}
+
+fn track_metadata(track: &ScrobbleTrack) -> Value {
+    let mut additional_info = json!({
+        "media_player": track.source,
+        "submission_client": "now-playing",
+        "submission_client_version": env!("CARGO_PKG_VERSION"),
+    });
+    if let Some(duration_ms) = track.duration_ms {
+        additional_info["duration_ms"] = json!(duration_ms);
+    }
+    let mut metadata = json!({
+        "artist_name": track.artist,
+        "track_name": track.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = track.album.as_ref() {
+        metadata["release_name"] = json!(album);
+    }
+    metadata
+}
+
+impl ScrobbleClient for ListenBrainzClient {
+    fn service(&self) -> ScrobbleService {
+        ScrobbleService::ListenBrainz
+    }
+
+    fn now_playing(&self, track: &ScrobbleTrack) -> Result<(), SubmitError> {
+        self.submit(
+            "playing_now",
+            json!({ "track_metadata": track_metadata(track) }),
+        )
+    }
+
+    fn scrobble(&self, track: &ScrobbleTrack, listened_at: i64) -> Result<(), SubmitError> {
+        self.submit(
+            "single",
+            json!({
+                "listened_at": listened_at,
+                "track_metadata": track_metadata(track),
+            }),
+        )
+    }
+}
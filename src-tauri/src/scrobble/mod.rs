@@ -0,0 +1,378 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend::BackendEvent,
+    history::HistoryEntry,
+    model::{ticks_to_ms, PlaybackStatus, SessionModel},
+};
+
+use lastfm::{LastFmClient, LastFmConfig};
+use listenbrainz::{ListenBrainzClient, ListenBrainzConfig};
+use queue::{QueuedScrobble, ScrobbleQueue};
+
+pub mod lastfm;
+pub mod listenbrainz;
+pub mod queue;
+
+// scrobbles that could not be sent yet survive restarts in this file
+pub const SCROBBLE_QUEUE_FILE_NAME: &str = "scrobble_queue.json";
+
+// the rules both services document: longer than 30s, and played for half of it or 4 minutes
+const MIN_TRACK_MS: i64 = 30_000;
+const MAX_REQUIRED_PLAYED_MS: i64 = 240_000;
+
+// doubled after every failed attempt, up to the maximum
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60 * 60);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const USER_AGENT: &str = concat!("now-playing/", env!("CARGO_PKG_VERSION"));
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ScrobbleService {
+    LastFm,
+    ListenBrainz,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrobbleTrack {
+    pub source: String,
+    pub title: String,
+    pub artist: String,
+    pub album: Option<String>,
+    pub duration_ms: Option<i64>,
+}
+
+impl ScrobbleTrack {
+    fn from_model(source: &str, model: &SessionModel) -> Option<Self> {
+        let media = model.media.as_ref().filter(|m| !m.title.is_empty())?;
+        let duration_ms = model
+            .timeline
+            .as_ref()
+            .filter(|t| t.end > t.start)
+            .map(|t| ticks_to_ms(t.end - t.start));
+        Some(Self {
+            source: source.to_string(),
+            title: media.title.clone(),
+            artist: media.artist.clone(),
+            album: media
+                .album
+                .as_ref()
+                .map(|a| a.title.clone())
+                .filter(|t| !t.is_empty()),
+            duration_ms,
+        })
+    }
+
+    // the duration often arrives in a later update than the metadata
+    fn is_same_track(&self, other: &ScrobbleTrack) -> bool {
+        self.title == other.title && self.artist == other.artist && self.album == other.album
+    }
+}
+
+impl From<&HistoryEntry> for ScrobbleTrack {
+    fn from(entry: &HistoryEntry) -> Self {
+        Self {
+            source: entry.source.clone(),
+            title: entry.title.clone(),
+            artist: entry.artist.clone(),
+            album: entry.album.clone(),
+            duration_ms: entry.duration_ms,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SubmitError {
+    // network trouble, rate limits, bad credentials, sent again later
+    Retry(String),
+    // the service will never accept this submission
+    Drop(String),
+}
+
+impl fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubmitError::Retry(message) => write!(f, "{} (will retry)", message),
+            SubmitError::Drop(message) => write!(f, "{} (dropped)", message),
+        }
+    }
+}
+
+pub trait ScrobbleClient: Send {
+    fn service(&self) -> ScrobbleService;
+    fn now_playing(&self, track: &ScrobbleTrack) -> Result<(), SubmitError>;
+    // listened_at is the unix time in seconds the track started playing
+    fn scrobble(&self, track: &ScrobbleTrack, listened_at: i64) -> Result<(), SubmitError>;
+}
+
+fn agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(USER_AGENT)
+        .build()
+}
+
+// the [scrobble] table of the settings file, with a table for every service to scrobble to
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScrobbleConfig {
+    pub lastfm: Option<LastFmConfig>,
+    pub listenbrainz: Option<ListenBrainzConfig>,
+}
+
+impl ScrobbleConfig {
+    fn clients(&self) -> Vec<Box<dyn ScrobbleClient>> {
+        let mut clients = Vec::<Box<dyn ScrobbleClient>>::new();
+        if let Some(config) = self.lastfm.clone() {
+            clients.push(Box::new(LastFmClient::new(config)));
+        }
+        if let Some(config) = self.listenbrainz.clone() {
+            clients.push(Box::new(ListenBrainzClient::new(config)));
+        }
+        clients
+    }
+}
+
+pub fn is_scrobblable(entry: &HistoryEntry) -> bool {
+    let Some(duration_ms) = entry.duration_ms else {
+        return false;
+    };
+    duration_ms > MIN_TRACK_MS && entry.played_ms >= (duration_ms / 2).min(MAX_REQUIRED_PLAYED_MS)
+}
+
+enum Job {
+    NowPlaying(ScrobbleTrack),
+    Scrobble(ScrobbleTrack, i64),
+}
+
+// follows the event stream and hands submissions to a worker thread, so requests never block it
+pub struct Scrobbler {
+    jobs: Sender<Job>,
+    now_playing: BTreeMap<usize, ScrobbleTrack>,
+}
+
+impl Scrobbler {
+    // None when no service is configured
+    pub fn start(config: ScrobbleConfig, queue_file: Option<PathBuf>) -> Option<Self> {
+        let clients = config.clients();
+        if clients.is_empty() {
+            return None;
+        }
+        let queue = ScrobbleQueue::open(queue_file);
+        let (jobs, rx) = mpsc::channel();
+        thread::spawn(move || run(clients, queue, rx));
+        Some(Self {
+            jobs,
+            now_playing: BTreeMap::new(),
+        })
+    }
+
+    // finished are the plays the history completed with this event
+    pub fn apply(&mut self, event: &BackendEvent, finished: &[HistoryEntry]) {
+        match event {
+            BackendEvent::SessionUpdate(update) => {
+                let model = &update.session_model;
+                let playing = model
+                    .playback
+                    .as_ref()
+                    .is_some_and(|p| p.status == PlaybackStatus::Playing);
+                match ScrobbleTrack::from_model(&update.source, model) {
+                    Some(track) if playing => {
+                        let announced = self
+                            .now_playing
+                            .get(&update.session_id)
+                            .is_some_and(|t| t.is_same_track(&track));
+                        if !announced {
+                            self.now_playing.insert(update.session_id, track.clone());
+                            self.send(Job::NowPlaying(track));
+                        }
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.now_playing.remove(&update.session_id);
+                    }
+                }
+            }
+            BackendEvent::SessionRemove(remove) => {
+                self.now_playing.remove(&remove.session_id);
+            }
+            BackendEvent::SessionCreate(_) | BackendEvent::ActiveSessionChange(_) => {}
+        }
+        for entry in finished.iter().filter(|e| is_scrobblable(e)) {
+            self.send(Job::Scrobble(entry.into(), entry.started_at_ms / 1000));
+        }
+    }
+
+    fn send(&self, job: Job) {
+        if self.jobs.send(job).is_err() {
+            println!("[scrobble] worker stopped");
+        }
+    }
+}
+
+fn run(clients: Vec<Box<dyn ScrobbleClient>>, mut queue: ScrobbleQueue, jobs: Receiver<Job>) {
+    let mut retry_delay = RETRY_DELAY;
+    // whatever was left over from the last run is sent right away
+    let mut next_retry = (!queue.is_empty()).then(Instant::now);
+    loop {
+        let job = match next_retry {
+            Some(at) => jobs.recv_timeout(at.saturating_duration_since(Instant::now())),
+            None => jobs.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match job {
+            Ok(Job::NowPlaying(track)) => {
+                // now playing is only useful right now, so it is never queued
+                for client in clients.iter() {
+                    if let Err(err) = client.now_playing(&track) {
+                        println!("[scrobble/{:?}] now playing: {}", client.service(), err);
+                    }
+                }
+                continue;
+            }
+            Ok(Job::Scrobble(track, listened_at)) => {
+                for client in clients.iter() {
+                    queue.push(QueuedScrobble {
+                        service: client.service(),
+                        track: track.clone(),
+                        listened_at,
+                    });
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+
+        if flush(&clients, &mut queue) {
+            retry_delay = RETRY_DELAY;
+            next_retry = None;
+        } else {
+            next_retry = Some(Instant::now() + retry_delay);
+            retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+        }
+    }
+}
+
+// sends queued scrobbles in order, returns whether none of them are left to retry
+fn flush(clients: &[Box<dyn ScrobbleClient>], queue: &mut ScrobbleQueue) -> bool {
+    // a service that failed once is skipped for the rest of the round, so its order is kept
+    let mut failed = Vec::new();
+    queue.retain(|item| {
+        if failed.contains(&item.service) {
+            return true;
+        }
+        // kept for when the service is configured again, its credentials may only be expired
+        let Some(client) = clients.iter().find(|c| c.service() == item.service) else {
+            return true;
+        };
+        match client.scrobble(&item.track, item.listened_at) {
+            Ok(()) => false,
+            Err(err @ SubmitError::Retry(_)) => {
+                println!("[scrobble/{:?}] {}", item.service, err);
+                failed.push(item.service);
+                true
+            }
+            Err(err @ SubmitError::Drop(_)) => {
+                println!("[scrobble/{:?}] {}", item.service, err);
+                false
+            }
+        }
+    });
+    failed.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex as SyncMutex};
+
+    use super::*;
+
+    // records the titles it was asked to scrobble
+    struct FakeClient {
+        service: ScrobbleService,
+        sent: Arc<SyncMutex<Vec<String>>>,
+    }
+
+    impl ScrobbleClient for FakeClient {
+        fn service(&self) -> ScrobbleService {
+            self.service
+        }
+
+        fn now_playing(&self, _track: &ScrobbleTrack) -> Result<(), SubmitError> {
+            Ok(())
+        }
+
+        fn scrobble(&self, track: &ScrobbleTrack, _listened_at: i64) -> Result<(), SubmitError> {
+            self.sent.lock().unwrap().push(track.title.clone());
+            Ok(())
+        }
+    }
+
+    fn entry(duration_ms: Option<i64>, played_ms: i64) -> HistoryEntry {
+        HistoryEntry {
+            source: "Spotify.exe".to_string(),
+            title: "Title".to_string(),
+            artist: "Artist".to_string(),
+            album: None,
+            duration_ms,
+            started_at_ms: 0,
+            ended_at_ms: played_ms,
+            played_ms,
+        }
+    }
+
+    #[test]
+    fn half_of_a_short_track_is_enough() {
+        assert!(is_scrobblable(&entry(Some(200_000), 100_000)));
+        assert!(!is_scrobblable(&entry(Some(200_000), 99_999)));
+    }
+
+    #[test]
+    fn long_tracks_need_at_most_four_minutes() {
+        let long = Some(20 * 60 * 1000);
+        assert!(is_scrobblable(&entry(long, MAX_REQUIRED_PLAYED_MS)));
+        assert!(!is_scrobblable(&entry(long, MAX_REQUIRED_PLAYED_MS - 1)));
+    }
+
+    #[test]
+    fn keeps_scrobbles_for_services_that_are_not_configured() {
+        let sent = Arc::new(SyncMutex::new(Vec::new()));
+        let client = |service| -> Box<dyn ScrobbleClient> {
+            Box::new(FakeClient {
+                service,
+                sent: Arc::clone(&sent),
+            })
+        };
+        let mut queue = ScrobbleQueue::open(None);
+        for service in [ScrobbleService::LastFm, ScrobbleService::ListenBrainz] {
+            queue.push(QueuedScrobble {
+                service,
+                track: (&entry(Some(200_000), 200_000)).into(),
+                listened_at: 0,
+            });
+        }
+
+        // nothing failed, so there is nothing to retry either
+        assert!(flush(&[client(ScrobbleService::LastFm)], &mut queue));
+        assert!(!queue.is_empty());
+        assert!(flush(&[client(ScrobbleService::ListenBrainz)], &mut queue));
+        assert!(queue.is_empty());
+        assert_eq!(sent.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn short_or_unknown_tracks_are_never_scrobbled() {
+        assert!(!is_scrobblable(&entry(Some(MIN_TRACK_MS), MIN_TRACK_MS)));
+        assert!(!is_scrobblable(&entry(None, 600_000)));
+    }
+}
@@ -0,0 +1,139 @@
+use std::{fs, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::{ScrobbleService, ScrobbleTrack};
+
+// one entry per service, so that each service keeps its own progress
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedScrobble {
+    pub service: ScrobbleService,
+    pub track: ScrobbleTrack,
+    pub listened_at: i64,
+}
+
+// the queue is small, so it is rewritten as a whole after every change
+pub struct ScrobbleQueue {
+    items: Vec<QueuedScrobble>,
+    file: Option<PathBuf>,
+}
+
+impl ScrobbleQueue {
+    // without a file the queue only lasts until the app exits
+    pub fn open(file: Option<PathBuf>) -> Self {
+        let items = match file.as_ref().map(fs::read) {
+            Some(Ok(data)) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+                println!("[scrobble] discarding unreadable queue: {}", err);
+                Vec::new()
+            }),
+            Some(Err(err)) if err.kind() != std::io::ErrorKind::NotFound => {
+                println!("[scrobble] failed to read queue: {}", err);
+                Vec::new()
+            }
+            _ => Vec::new(),
+        };
+        Self { items, file }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn push(&mut self, item: QueuedScrobble) {
+        self.items.push(item);
+        self.save();
+    }
+
+    pub fn retain(&mut self, f: impl FnMut(&QueuedScrobble) -> bool) {
+        let len = self.items.len();
+        self.items.retain(f);
+        if self.items.len() != len {
+            self.save();
+        }
+    }
+
+    fn save(&self) {
+        let Some(file) = self.file.as_ref() else {
+            return;
+        };
+        // written next to the queue and renamed, so a crash never leaves half a queue
+        let tmp = file.with_extension("json.tmp");
+        let result = serde_json::to_vec(&self.items)
+            .map_err(std::io::Error::from)
+            .and_then(|data| {
+                if let Some(dir) = file.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&tmp, data)?;
+                fs::rename(&tmp, file)
+            });
+        if let Err(err) = result {
+            println!("[scrobble] failed to save queue: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queued(service: ScrobbleService, title: &str) -> QueuedScrobble {
+        QueuedScrobble {
+            service,
+            track: ScrobbleTrack {
+                source: "Spotify.exe".to_string(),
+                title: title.to_string(),
+                artist: "Artist".to_string(),
+                album: None,
+                duration_ms: Some(200_000),
+            },
+            listened_at: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn survives_reopening() {
+        let dir = std::env::temp_dir().join(format!("now-playing-queue-{}", std::process::id()));
+        let file = dir.join("scrobble_queue.json");
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut queue = ScrobbleQueue::open(Some(file.clone()));
+        assert!(queue.is_empty());
+        queue.push(queued(ScrobbleService::LastFm, "One"));
+        queue.push(queued(ScrobbleService::ListenBrainz, "One"));
+        queue.push(queued(ScrobbleService::LastFm, "Two"));
+        queue.retain(|q| q.service != ScrobbleService::ListenBrainz);
+
+        let reopened = ScrobbleQueue::open(Some(file.clone()));
+        assert_eq!(
+            reopened.items,
+            vec![
+                queued(ScrobbleService::LastFm, "One"),
+                queued(ScrobbleService::LastFm, "Two"),
+            ]
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discards_an_unreadable_file() {
+        let dir =
+            std::env::temp_dir().join(format!("now-playing-bad-queue-{}", std::process::id()));
+        let file = dir.join("scrobble_queue.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&file, "not json").unwrap();
+
+        assert!(ScrobbleQueue::open(Some(file)).is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn lives_in_memory_without_a_file() {
+        let mut queue = ScrobbleQueue::open(None);
+        queue.push(queued(ScrobbleService::LastFm, "One"));
+        assert!(!queue.is_empty());
+        queue.retain(|_| false);
+        assert!(queue.is_empty());
+    }
+}
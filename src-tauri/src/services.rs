@@ -0,0 +1,113 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex as SyncMutex},
+};
+
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    api::{feed::EventFeed, ApiServer},
+    artwork::ArtworkCache,
+    backend::BackendEvent,
+    discord::DiscordPresence,
+    history::HistoryEntry,
+    output::FileOutput,
+    position::now_ms,
+    scrobble::Scrobbler,
+    settings::Settings,
+    store::SessionStore,
+};
+
+// the optional parts of the app that are set up in the settings file. each one is started,
+// stopped or restarted on its own whenever its table changes
+pub struct Services {
+    // scrobbles that could not be sent yet wait here, None keeps them in memory
+    queue_file: Option<PathBuf>,
+    api: Option<ApiServer>,
+    scrobbler: Option<Scrobbler>,
+    output: Option<FileOutput>,
+    discord: Option<DiscordPresence>,
+}
+
+impl Services {
+    pub fn new(queue_file: Option<PathBuf>) -> Self {
+        Self {
+            queue_file,
+            api: None,
+            scrobbler: None,
+            output: None,
+            discord: None,
+        }
+    }
+
+    // on startup previous are the defaults, which leave every service off
+    pub fn configure(&mut self, handle: &AppHandle, previous: &Settings, settings: &Settings) {
+        if previous.api != settings.api {
+            // stopped first, a restart usually wants the same port
+            self.api = None;
+            if let Some(config) = settings.api.clone() {
+                let feed = Arc::clone(&handle.state::<Arc<EventFeed>>());
+                match ApiServer::start(handle.clone(), config, feed) {
+                    Ok(server) => self.api = Some(server),
+                    Err(err) => println!("{}", err),
+                }
+            }
+        }
+        if previous.scrobble != settings.scrobble {
+            self.scrobbler = Scrobbler::start(settings.scrobble.clone(), self.queue_file.clone());
+        }
+        if previous.output != settings.output {
+            self.output = match settings.output.clone().map(FileOutput::new) {
+                Some(Ok(o)) => Some(o),
+                Some(Err(err)) => {
+                    println!("{}", err);
+                    None
+                }
+                None => None,
+            };
+        }
+        if previous.discord != settings.discord {
+            // the old presence clears itself once dropped
+            self.discord = None;
+            self.discord = match settings.discord.clone().map(DiscordPresence::start) {
+                Some(Ok(p)) => Some(p),
+                Some(Err(err)) => {
+                    println!("{}", err);
+                    None
+                }
+                None => None,
+            };
+        }
+    }
+
+    // called for every event, after the store and the history have applied it
+    pub fn apply(
+        &mut self,
+        event: &BackendEvent,
+        finished: &[HistoryEntry],
+        store: &SyncMutex<SessionStore>,
+        artwork: &ArtworkCache,
+    ) {
+        if let Some(s) = self.scrobbler.as_mut() {
+            s.apply(event, finished);
+        }
+        // both follow the active session rather than the event itself
+        if self.output.is_none() && self.discord.is_none() {
+            return;
+        }
+        let (active, position) = {
+            let store = store.lock().unwrap();
+            let active = store.active().cloned();
+            let position = active
+                .as_ref()
+                .and_then(|s| store.position(s.session_id, now_ms()));
+            (active, position)
+        };
+        if let Some(o) = self.output.as_mut() {
+            o.apply(active.as_ref(), position.as_ref(), artwork);
+        }
+        if let Some(d) = self.discord.as_mut() {
+            d.apply(active.as_ref(), position.as_ref());
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex as SyncMutex},
+    thread,
+    time::Duration,
+};
+
+use global_hotkey::hotkey::HotKey;
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::{
+    api::ApiConfig,
+    artwork::ArtworkCache,
+    discord::DiscordConfig,
+    emit_event,
+    error::MediaError,
+    hotkey::{HotkeyConfig, Hotkeys},
+    instance,
+    output::{write_atomic, OutputConfig},
+    scrobble::ScrobbleConfig,
+    services::Services,
+    template::Template,
+    thumbnail::{self, ThumbnailOptions},
+};
+
+// in the app config dir
+pub const SETTINGS_FILE_NAME: &str = "settings.toml";
+// bumped with every change to the file layout that older files would be misread by
+pub const SETTINGS_VERSION: u32 = 1;
+
+// editors save in several steps, the file is read once they are done
+const RELOAD_DELAY: Duration = Duration::from_millis(250);
+// larger than any screen, smaller sizes are up to the user
+const MAX_WINDOW_SIZE: u32 = 8192;
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Settings {
+    pub version: u32,
+    pub window: WindowSettings,
+    pub hotkeys: HotkeyConfig,
+    pub thumbnails: ThumbnailOptions,
+    // the services below are off while their table is missing
+    pub api: Option<ApiConfig>,
+    pub scrobble: ScrobbleConfig,
+    pub output: Option<OutputConfig>,
+    pub discord: Option<DiscordConfig>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            window: WindowSettings::default(),
+            hotkeys: HotkeyConfig::default(),
+            thumbnails: ThumbnailOptions::default(),
+            api: None,
+            scrobble: ScrobbleConfig::default(),
+            output: None,
+            discord: None,
+        }
+    }
+}
+
+// sizes and distances are physical pixels
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WindowSettings {
+    pub normal_width: u32,
+    pub normal_height: u32,
+    pub mini_width: u32,
+    pub mini_height: u32,
+    // a window moved closer than this to the bottom of the work area turns into the mini bar
+    pub mini_mode_edge: u32,
+    // for normal mode, the mini bar is always on top
+    pub always_on_top: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            normal_width: 300,
+            normal_height: 300,
+            mini_width: 500,
+            mini_height: 50,
+            mini_mode_edge: 50,
+            always_on_top: false,
+        }
+    }
+}
+
+impl Settings {
+    // a missing file has the defaults
+    pub fn load(file: &Path) -> Result<Self, MediaError> {
+        let content = match fs::read_to_string(file) {
+            Ok(c) => c,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(MediaError::storage_failed("Failed to read settings.").with_source(err));
+            }
+        };
+        let settings = match toml::from_str::<Settings>(&content) {
+            Ok(s) => s,
+            Err(err) => {
+                return Err(
+                    MediaError::conversion_failed("Failed to parse settings.").with_source(err)
+                );
+            }
+        };
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    pub fn save(&self, file: &Path) -> Result<(), MediaError> {
+        let content = match toml::to_string_pretty(self) {
+            Ok(c) => c,
+            Err(err) => {
+                return Err(
+                    MediaError::conversion_failed("Failed to serialize settings.").with_source(err),
+                );
+            }
+        };
+        let result = match file.parent() {
+            Some(dir) => fs::create_dir_all(dir),
+            None => Ok(()),
+        }
+        .and_then(|()| write_atomic(file, content.as_bytes()));
+        match result {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                Err(MediaError::storage_failed("Failed to write settings.").with_source(err))
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), MediaError> {
+        // there is only one layout so far, any other version is from a newer app
+        if self.version != SETTINGS_VERSION {
+            return Err(MediaError::conversion_failed(format!(
+                "Settings version {} is not {SETTINGS_VERSION}.",
+                self.version
+            )));
+        }
+        let window = &self.window;
+        let sizes = [
+            ("normalWidth", window.normal_width),
+            ("normalHeight", window.normal_height),
+            ("miniWidth", window.mini_width),
+            ("miniHeight", window.mini_height),
+        ];
+        for (name, size) in sizes {
+            if size == 0 || size > MAX_WINDOW_SIZE {
+                return Err(MediaError::conversion_failed(format!(
+                    "{name} must be between 1 and {MAX_WINDOW_SIZE}."
+                )));
+            }
+        }
+        if window.mini_mode_edge == 0 || window.mini_mode_edge > window.normal_height {
+            return Err(MediaError::conversion_failed(
+                "miniModeEdge must be between 1 and normalHeight.",
+            ));
+        }
+        for shortcut in self.hotkeys.bindings.keys() {
+            if let Err(err) = shortcut.parse::<HotKey>() {
+                return Err(MediaError::conversion_failed(format!(
+                    "Invalid hotkey {shortcut}: {err}."
+                )));
+            }
+        }
+        Template::parse(&self.hotkeys.copy_format)?;
+        let thumbnails = [
+            ("miniSize", self.thumbnails.mini_size),
+            ("normalSize", self.thumbnails.normal_size),
+        ];
+        for (name, size) in thumbnails {
+            if size == 0 || size > thumbnail::MAX_SIZE {
+                return Err(MediaError::conversion_failed(format!(
+                    "{name} must be between 1 and {}.",
+                    thumbnail::MAX_SIZE
+                )));
+            }
+        }
+        if let Some(api) = self.api.as_ref() {
+            if api.port == 0 {
+                return Err(MediaError::conversion_failed("The api port must not be 0."));
+            }
+            if api.token.as_deref() == Some("") {
+                return Err(MediaError::conversion_failed(
+                    "The api token must not be empty, leave it out to go without one.",
+                ));
+            }
+        }
+        if let Some(output) = self.output.as_ref() {
+            output.validate()?;
+        }
+        if let Some(discord) = self.discord.as_ref() {
+            discord.validate()?;
+        }
+        Ok(())
+    }
+}
+
+// the settings as the webview gets them, in answers as well as in events. the credentials are
+// only shown as whether they are set, they are only ever set in the file
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsView {
+    pub version: u32,
+    pub window: WindowSettings,
+    pub hotkeys: HotkeyConfig,
+    pub thumbnails: ThumbnailOptions,
+    pub api: Option<ApiView>,
+    pub scrobble: ScrobbleView,
+    pub output: Option<OutputConfig>,
+    pub discord: Option<DiscordConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ApiView {
+    pub port: u16,
+    pub has_token: bool,
+}
+
+// true for every service with credentials in the file
+#[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ScrobbleView {
+    pub lastfm: bool,
+    pub listenbrainz: bool,
+}
+
+impl Settings {
+    pub fn view(&self) -> SettingsView {
+        SettingsView {
+            version: self.version,
+            window: self.window.clone(),
+            hotkeys: self.hotkeys.clone(),
+            thumbnails: self.thumbnails.clone(),
+            api: self.api.as_ref().map(|a| ApiView {
+                port: a.port,
+                has_token: a.token.is_some(),
+            }),
+            scrobble: ScrobbleView {
+                lastfm: self.scrobble.lastfm.is_some(),
+                listenbrainz: self.scrobble.listenbrainz.is_some(),
+            },
+            output: self.output.clone(),
+            discord: self.discord.clone(),
+        }
+    }
+}
+
+impl SettingsView {
+    // the credentials come from the current settings. the webview can drop them, which turns a
+    // service off, but a token or a scrobbler it asks for without them stays off
+    fn into_settings(self, current: &Settings) -> Settings {
+        Settings {
+            version: self.version,
+            window: self.window,
+            hotkeys: self.hotkeys,
+            thumbnails: self.thumbnails,
+            api: self.api.map(|a| ApiConfig {
+                port: a.port,
+                token: current
+                    .api
+                    .as_ref()
+                    .and_then(|c| c.token.clone())
+                    .filter(|_| a.has_token),
+            }),
+            scrobble: ScrobbleConfig {
+                lastfm: current
+                    .scrobble
+                    .lastfm
+                    .clone()
+                    .filter(|_| self.scrobble.lastfm),
+                listenbrainz: current
+                    .scrobble
+                    .listenbrainz
+                    .clone()
+                    .filter(|_| self.scrobble.listenbrainz),
+            },
+            output: self.output,
+            discord: self.discord,
+        }
+    }
+}
+
+// the settings of the running app and the file they are kept in
+pub struct SettingsState {
+    // None without a config dir, the settings then only last until the app closes
+    file: Option<PathBuf>,
+    settings: SyncMutex<Settings>,
+}
+
+impl SettingsState {
+    // an unreadable or invalid file is reported and left alone, the app runs on the defaults
+    pub fn open(file: Option<PathBuf>) -> Self {
+        let settings = match file.as_deref().map(Settings::load) {
+            Some(Ok(s)) => s,
+            Some(Err(err)) => {
+                println!("[settings] {}", err);
+                Settings::default()
+            }
+            None => Settings::default(),
+        };
+        Self {
+            file,
+            settings: SyncMutex::new(settings),
+        }
+    }
+
+    pub fn get(&self) -> Settings {
+        self.settings.lock().unwrap().clone()
+    }
+
+    pub fn window(&self) -> WindowSettings {
+        self.settings.lock().unwrap().window.clone()
+    }
+
+    // replaces the settings and returns the previous ones, None when nothing changed
+    fn replace(&self, settings: Settings, save: bool) -> Result<Option<Settings>, MediaError> {
+        let mut current = self.settings.lock().unwrap();
+        if *current == settings {
+            return Ok(None);
+        }
+        if let (true, Some(file)) = (save, self.file.as_deref()) {
+            settings.save(file)?;
+        }
+        Ok(Some(std::mem::replace(&mut *current, settings)))
+    }
+}
+
+pub fn update(handle: &AppHandle, view: SettingsView) -> Result<SettingsView, MediaError> {
+    let state = handle.state::<SettingsState>();
+    let settings = view.into_settings(&state.get());
+    settings.validate()?;
+    if let Some(previous) = state.replace(settings.clone(), true)? {
+        apply(handle, &previous, &settings);
+    }
+    Ok(settings.view())
+}
+
+// pushes changed settings to the parts of the app that hold on to them
+fn apply(handle: &AppHandle, previous: &Settings, settings: &Settings) {
+    if previous.window != settings.window {
+        if let Err(err) = instance::apply_window_settings(handle, &settings.window) {
+            println!("[settings] {}", err);
+        }
+    }
+    if previous.hotkeys != settings.hotkeys {
+        // only managed once the hotkeys started
+        if let Some(hotkeys) = handle.try_state::<Hotkeys>() {
+            if let Err(err) = hotkeys.register(settings.hotkeys.clone()) {
+                println!("[settings] {}", err);
+            }
+        }
+    }
+    if previous.thumbnails != settings.thumbnails {
+        if let Some(artwork) = handle.try_state::<Arc<ArtworkCache>>() {
+            artwork.set_options(settings.thumbnails.clone());
+        }
+    }
+    // only managed once setup started them
+    if let Some(services) = handle.try_state::<Arc<SyncMutex<Services>>>() {
+        services
+            .lock()
+            .unwrap()
+            .configure(handle, previous, settings);
+    }
+    emit_event("settings_changed", settings.view(), handle);
+}
+
+// reloads the file when it is edited outside the app, for as long as the app runs
+pub fn watch(handle: AppHandle, file: PathBuf) -> Result<(), MediaError> {
+    let Some(dir) = file.parent().map(Path::to_path_buf) else {
+        return Err(MediaError::not_found("The settings file has no directory."));
+    };
+    let (tx, rx) = mpsc::channel();
+    // the directory is watched since saving often replaces the file instead of writing to it
+    let watcher = fs::create_dir_all(&dir)
+        .map_err(notify::Error::io)
+        .and_then(|()| notify::recommended_watcher(tx))
+        .and_then(|mut w| w.watch(&dir, RecursiveMode::NonRecursive).map(|()| w));
+    let watcher = match watcher {
+        Ok(w) => w,
+        Err(err) => {
+            return Err(
+                MediaError::service_failed("Failed to watch the settings file.").with_source(err),
+            );
+        }
+    };
+    thread::spawn(move || {
+        // dropping the watcher stops the events
+        let _watcher = watcher;
+        // by name, the watcher may report the directory through another path. reading the file
+        // is an event as well, which would otherwise reload it again and again
+        let is_settings = |event: &notify::Event| {
+            !event.kind.is_access()
+                && event
+                    .paths
+                    .iter()
+                    .any(|p| p.file_name() == file.file_name())
+        };
+        while let Ok(event) = rx.recv() {
+            match event {
+                Ok(e) if is_settings(&e) => {}
+                Ok(_) => continue,
+                Err(err) => {
+                    println!("[settings] {}", err);
+                    continue;
+                }
+            }
+            // everything arriving while the editor is still busy is part of the same save
+            while rx.recv_timeout(RELOAD_DELAY).is_ok() {}
+            reload(&handle, &file);
+        }
+    });
+    Ok(())
+}
+
+fn reload(handle: &AppHandle, file: &Path) {
+    // a file that does not load keeps the settings as they are, the next save may fix it
+    let settings = match Settings::load(file) {
+        Ok(s) => s,
+        Err(err) => {
+            println!("[settings] {}", err);
+            return;
+        }
+    };
+    let state = handle.state::<SettingsState>();
+    match state.replace(settings.clone(), false) {
+        Ok(Some(previous)) => {
+            println!("[settings] reloaded {}", file.display());
+            apply(handle, &previous, &settings);
+        }
+        // the app's own saves end up here as well
+        Ok(None) => {}
+        Err(err) => println!("[settings] {}", err),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scrobble::{lastfm::LastFmConfig, listenbrainz::ListenBrainzConfig};
+
+    fn with_credentials() -> Settings {
+        Settings {
+            api: Some(ApiConfig {
+                port: 9000,
+                token: Some("api-token".to_string()),
+            }),
+            scrobble: ScrobbleConfig {
+                lastfm: Some(LastFmConfig {
+                    api_key: "key".to_string(),
+                    api_secret: "lastfm-secret".to_string(),
+                    session_key: "lastfm-session".to_string(),
+                    api_url: "http://localhost".to_string(),
+                }),
+                listenbrainz: Some(ListenBrainzConfig {
+                    token: "listenbrainz-token".to_string(),
+                    api_url: "http://localhost".to_string(),
+                }),
+            },
+            ..Settings::default()
+        }
+    }
+
+    #[test]
+    fn the_view_leaves_out_credentials() {
+        let json = serde_json::to_string(&with_credentials().view()).unwrap();
+        for secret in [
+            "api-token",
+            "lastfm-secret",
+            "lastfm-session",
+            "listenbrainz-token",
+        ] {
+            assert!(!json.contains(secret), "{secret} in {json}");
+        }
+    }
+
+    #[test]
+    fn credentials_survive_a_round_trip_through_the_view() {
+        let current = with_credentials();
+        let mut view = current.view();
+        view.window.always_on_top = true;
+        let updated = view.into_settings(&current);
+        assert!(updated.window.always_on_top);
+        assert_eq!(updated.api, current.api);
+        assert_eq!(updated.scrobble, current.scrobble);
+
+        // dropped services stay dropped, they are only set up again in the file
+        let mut view = current.view();
+        view.api.as_mut().unwrap().has_token = false;
+        view.scrobble.lastfm = false;
+        let updated = view.into_settings(&current);
+        assert_eq!(updated.api.unwrap().token, None);
+        assert_eq!(updated.scrobble.lastfm, None);
+        assert!(updated.scrobble.listenbrainz.is_some());
+        assert_eq!(
+            Settings::default().view().into_settings(&current),
+            Settings::default()
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+
+use crate::{
+    backend::BackendEvent,
+    model::{CurrentSession, SessionModel},
+    position::{now_ms, PlaybackClock, SessionPosition},
+};
+
+// the latest known state of every session, kept up to date by the event loop
+#[derive(Default)]
+pub struct SessionStore {
+    sessions: BTreeMap<usize, CurrentSession>,
+    clocks: BTreeMap<usize, PlaybackClock>,
+    active: Option<usize>,
+}
+
+impl SessionStore {
+    pub fn apply(&mut self, event: &BackendEvent) {
+        match event {
+            BackendEvent::SessionCreate(create) => {
+                self.sessions.insert(
+                    create.session_id,
+                    CurrentSession {
+                        session_id: create.session_id,
+                        source: create.source.clone(),
+                        session: SessionModel {
+                            playback: None,
+                            timeline: None,
+                            media: None,
+                            source: create.source.clone(),
+                        },
+                        art_id: None,
+                        palette: None,
+                    },
+                );
+            }
+            BackendEvent::SessionUpdate(update) => {
+                let Some(session) = self.sessions.get_mut(&update.session_id) else {
+                    println!("[store] update for unknown session {}", update.session_id);
+                    return;
+                };
+                session.session = update.session_model.clone();
+                match PlaybackClock::update(
+                    self.clocks.get(&update.session_id),
+                    &update.session_model,
+                    now_ms(),
+                ) {
+                    Some(clock) => self.clocks.insert(update.session_id, clock),
+                    None => self.clocks.remove(&update.session_id),
+                };
+                // updates without art keep the previous art
+                if update.art_id.is_some() {
+                    session.art_id = update.art_id.clone();
+                    session.palette = update.palette.clone();
+                }
+            }
+            BackendEvent::SessionRemove(remove) => {
+                self.sessions.remove(&remove.session_id);
+                self.clocks.remove(&remove.session_id);
+                if self.active == Some(remove.session_id) {
+                    self.active = None;
+                }
+            }
+            BackendEvent::ActiveSessionChange(change) => {
+                self.active = change.as_ref().map(|c| c.session_id);
+            }
+        }
+    }
+
+    pub fn get(&self, session_id: usize) -> Option<&CurrentSession> {
+        self.sessions.get(&session_id)
+    }
+
+    pub fn active(&self) -> Option<&CurrentSession> {
+        self.active.and_then(|id| self.sessions.get(&id))
+    }
+
+    pub fn current_sessions(&self) -> Vec<CurrentSession> {
+        self.sessions.values().cloned().collect()
+    }
+
+    pub fn position(&self, session_id: usize, now_ms: i64) -> Option<SessionPosition> {
+        let clock = self.clocks.get(&session_id)?;
+        Some(clock.snapshot(session_id, now_ms))
+    }
+
+    pub fn positions(&self, now_ms: i64) -> Vec<SessionPosition> {
+        self.clocks
+            .iter()
+            .map(|(&session_id, clock)| clock.snapshot(session_id, now_ms))
+            .collect()
+    }
+}
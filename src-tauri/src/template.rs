@@ -0,0 +1,506 @@
+use crate::{
+    error::MediaError,
+    model::{ticks_to_ms, PlaybackStatus, SessionModel},
+    position::SessionPosition,
+};
+
+// appended by truncate when it cuts something off
+const ELLIPSIS: char = '…';
+
+// every field a template can use, fields without a value render as ""
+pub const FIELDS: [&str; 14] = [
+    "title",
+    "subtitle",
+    "artist",
+    "album",
+    "album.title",
+    "album.artist",
+    "album.track_count",
+    "track_number",
+    "genres",
+    "source",
+    "position",
+    "duration",
+    "remaining",
+    "status",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Upper,
+    Lower,
+    Trim,
+    // at most n characters, including the ellipsis
+    Truncate(usize),
+    // used when the value is empty
+    Default(String),
+}
+
+impl Filter {
+    fn parse(source: &str) -> Result<Self, String> {
+        let (name, args) = match source.split_once('(') {
+            Some((name, rest)) => match rest.strip_suffix(')') {
+                Some(args) => (name.trim(), Some(args.trim())),
+                None => return Err(format!("missing ) after {source}")),
+            },
+            None => (source, None),
+        };
+        match (name, args) {
+            ("upper", None) => Ok(Filter::Upper),
+            ("lower", None) => Ok(Filter::Lower),
+            ("trim", None) => Ok(Filter::Trim),
+            ("truncate", Some(n)) => match n.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Filter::Truncate(n)),
+                _ => Err(format!("truncate expects a positive length, got {n}")),
+            },
+            ("default", Some(value)) => parse_string(value).map(Filter::Default),
+            ("upper" | "lower" | "trim", Some(_)) => Err(format!("{name} takes no arguments")),
+            ("truncate" | "default", None) => Err(format!("{name} expects an argument")),
+            _ => Err(format!("unknown filter {name}")),
+        }
+    }
+
+    fn apply(&self, value: String) -> String {
+        match self {
+            Filter::Upper => value.to_uppercase(),
+            Filter::Lower => value.to_lowercase(),
+            Filter::Trim => value.trim().to_string(),
+            Filter::Truncate(n) if value.chars().count() > *n => {
+                let mut cut = value.chars().take(n - 1).collect::<String>();
+                cut.truncate(cut.trim_end().len());
+                cut.push(ELLIPSIS);
+                cut
+            }
+            Filter::Truncate(_) => value,
+            Filter::Default(fallback) if value.is_empty() => fallback.clone(),
+            Filter::Default(_) => value,
+        }
+    }
+}
+
+// a field followed by filters, "title | truncate(20) | upper"
+#[derive(Debug, Clone, PartialEq)]
+struct Expr {
+    field: &'static str,
+    filters: Vec<Filter>,
+}
+
+impl Expr {
+    fn parse(source: &str) -> Result<Self, String> {
+        let mut parts = split_outside_quotes(source, '|').into_iter();
+        let name = parts.next().unwrap_or_default().trim();
+        let Some(field) = FIELDS.iter().find(|f| **f == name) else {
+            return Err(format!("unknown field {name:?}"));
+        };
+        let filters = parts
+            .map(|f| Filter::parse(f.trim()))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { field, filters })
+    }
+
+    fn eval(&self, ctx: &TemplateContext) -> String {
+        self.filters
+            .iter()
+            .fold(ctx.field(self.field), |value, filter| filter.apply(value))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    // the value is not empty
+    Present(Expr),
+    Equals(Expr, String),
+}
+
+impl Condition {
+    fn parse(source: &str) -> Result<(Self, bool), String> {
+        let (negated, source) = match source.trim().strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, source),
+        };
+        // "a != b" is "!(a == b)", so that there is a single kind of comparison
+        if let Some((expr, value)) = source.split_once("!=") {
+            let condition = Condition::Equals(Expr::parse(expr)?, parse_string(value.trim())?);
+            Ok((condition, !negated))
+        } else if let Some((expr, value)) = source.split_once("==") {
+            let condition = Condition::Equals(Expr::parse(expr)?, parse_string(value.trim())?);
+            Ok((condition, negated))
+        } else {
+            Ok((Condition::Present(Expr::parse(source)?), negated))
+        }
+    }
+
+    fn eval(&self, ctx: &TemplateContext) -> bool {
+        match self {
+            Condition::Present(expr) => !expr.eval(ctx).is_empty(),
+            Condition::Equals(expr, value) => expr.eval(ctx).eq_ignore_ascii_case(value),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Text(String),
+    Expr(Expr),
+    If {
+        condition: Condition,
+        negated: bool,
+        then: Vec<Node>,
+        otherwise: Vec<Node>,
+    },
+}
+
+// "{artist} - {title}{if album} [{album}]{end}", with "{{" and "}}" for literal braces.
+// conditions are "{if field}", "{if !field}" or "{if status == \"Playing\"}", and can have an
+// "{else}". fields take filters: upper, lower, trim, truncate(n) and default("text")
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    source: String,
+    nodes: Vec<Node>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, MediaError> {
+        match parse_nodes(source) {
+            Ok(nodes) => Ok(Self {
+                source: source.to_string(),
+                nodes,
+            }),
+            Err(reason) => Err(MediaError::conversion_failed(format!(
+                "Invalid template {source:?}: {reason}."
+            ))),
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn render(&self, ctx: &TemplateContext) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, ctx, &mut out);
+        out
+    }
+}
+
+// what a template is rendered against. the position is the timeline position unless a more
+// recent one from the store is given
+pub struct TemplateContext<'a> {
+    pub model: &'a SessionModel,
+    pub position: Option<&'a SessionPosition>,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn new(model: &'a SessionModel, position: Option<&'a SessionPosition>) -> Self {
+        Self { model, position }
+    }
+
+    fn field(&self, name: &str) -> String {
+        let media = self.model.media.as_ref();
+        let album = media.and_then(|m| m.album.as_ref());
+        match name {
+            "title" => media.map(|m| m.title.clone()),
+            "subtitle" => media.map(|m| m.subtitle.clone()),
+            "artist" => media.map(|m| m.artist.clone()),
+            "album" | "album.title" => album.map(|a| a.title.clone()),
+            "album.artist" => album.map(|a| a.artist.clone()),
+            "album.track_count" => album
+                .map(|a| a.track_count)
+                .filter(|&n| n > 0)
+                .map(|n| n.to_string()),
+            "track_number" => media
+                .and_then(|m| m.track_number)
+                .filter(|&n| n > 0)
+                .map(|n| n.to_string()),
+            "genres" => media.map(|m| m.genres.join(", ")),
+            "source" => Some(self.model.source.clone()),
+            "position" => self.position_ms().map(format_duration),
+            "duration" => self.duration_ms().map(format_duration),
+            "remaining" => self
+                .duration_ms()
+                .zip(self.position_ms())
+                .map(|(duration, position)| format_duration(duration - position)),
+            "status" => self.model.playback.as_ref().and_then(|p| match p.status {
+                PlaybackStatus::Playing => Some("Playing".to_string()),
+                PlaybackStatus::Paused => Some("Paused".to_string()),
+                PlaybackStatus::Stopped => Some("Stopped".to_string()),
+                _ => None,
+            }),
+            _ => None,
+        }
+        .unwrap_or_default()
+    }
+
+    fn duration_ms(&self) -> Option<i64> {
+        let timeline = self.model.timeline.as_ref().filter(|t| t.end > t.start)?;
+        Some(ticks_to_ms(timeline.end - timeline.start))
+    }
+
+    fn position_ms(&self) -> Option<i64> {
+        let (position, start) = match self.position {
+            Some(p) => (p.position, p.start),
+            None => {
+                let timeline = self.model.timeline.as_ref()?;
+                (timeline.position, timeline.start)
+            }
+        };
+        Some(ticks_to_ms(position - start).max(0))
+    }
+}
+
+// m:ss, or h:mm:ss from an hour on
+pub fn format_duration(ms: i64) -> String {
+    let total_secs = ms.max(0) / 1000;
+    let (hours, mins, secs) = (total_secs / 3600, total_secs / 60 % 60, total_secs % 60);
+    if hours > 0 {
+        format!("{hours}:{mins:02}:{secs:02}")
+    } else {
+        format!("{mins}:{secs:02}")
+    }
+}
+
+fn render_nodes(nodes: &[Node], ctx: &TemplateContext, out: &mut String) {
+    for node in nodes.iter() {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Expr(expr) => out.push_str(&expr.eval(ctx)),
+            Node::If {
+                condition,
+                negated,
+                then,
+                otherwise,
+            } => {
+                let branch = if condition.eval(ctx) != *negated {
+                    then
+                } else {
+                    otherwise
+                };
+                render_nodes(branch, ctx, out);
+            }
+        }
+    }
+}
+
+// an {if} that is still waiting for its {end}
+struct OpenIf {
+    condition: Condition,
+    negated: bool,
+    then: Option<Vec<Node>>,
+}
+
+fn parse_nodes(source: &str) -> Result<Vec<Node>, String> {
+    // the innermost open {if} is last, its nodes so far are the last entry of `levels`
+    let mut open = Vec::<OpenIf>::new();
+    let mut levels = vec![Vec::<Node>::new()];
+    let mut text = String::new();
+    let mut rest = source;
+
+    while let Some(i) = rest.find(['{', '}']) {
+        text.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            text.push_str(&rest[..1]);
+            rest = &rest[2..];
+            continue;
+        }
+        if rest.starts_with('}') {
+            return Err("unmatched }, write }} for a literal brace".to_string());
+        }
+        let Some(end) = tag_end(rest) else {
+            return Err("unclosed {, write {{ for a literal brace".to_string());
+        };
+        let tag = rest[1..end].trim();
+        rest = &rest[end + 1..];
+
+        let nodes = levels.last_mut().unwrap();
+        if !text.is_empty() {
+            nodes.push(Node::Text(std::mem::take(&mut text)));
+        }
+        if let Some(condition) = tag.strip_prefix("if ") {
+            let (condition, negated) = Condition::parse(condition)?;
+            open.push(OpenIf {
+                condition,
+                negated,
+                then: None,
+            });
+            levels.push(Vec::new());
+        } else if tag == "else" {
+            let Some(current) = open.last_mut().filter(|o| o.then.is_none()) else {
+                return Err("{else} without {if}".to_string());
+            };
+            current.then = Some(levels.pop().unwrap());
+            levels.push(Vec::new());
+        } else if tag == "end" {
+            let Some(current) = open.pop() else {
+                return Err("{end} without {if}".to_string());
+            };
+            let last = levels.pop().unwrap();
+            let (then, otherwise) = match current.then {
+                Some(then) => (then, last),
+                None => (last, Vec::new()),
+            };
+            levels.last_mut().unwrap().push(Node::If {
+                condition: current.condition,
+                negated: current.negated,
+                then,
+                otherwise,
+            });
+        } else {
+            nodes.push(Node::Expr(Expr::parse(tag)?));
+        }
+    }
+    if !open.is_empty() {
+        return Err("{if} without {end}".to_string());
+    }
+    text.push_str(rest);
+    let mut nodes = levels.pop().unwrap();
+    if !text.is_empty() {
+        nodes.push(Node::Text(text));
+    }
+    Ok(nodes)
+}
+
+// index of the } closing the tag at the start of source, skipping quoted arguments
+fn tag_end(source: &str) -> Option<usize> {
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in source.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '}' if !quoted => return Some(i),
+            '{' if !quoted => return None,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn split_outside_quotes(source: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in source.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => {
+                parts.push(&source[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&source[start..]);
+    parts
+}
+
+// "text" with \" and \\ escapes
+fn parse_string(source: &str) -> Result<String, String> {
+    let Some(inner) = source
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .filter(|_| source.len() >= 2)
+    else {
+        return Err(format!("expected a quoted string, got {source}"));
+    };
+    let mut value = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some(escaped @ ('"' | '\\')) => value.push(escaped),
+                _ => return Err(format!("invalid escape in {source}")),
+            },
+            '"' => return Err(format!("unescaped \" in {source}")),
+            c => value.push(c),
+        }
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{test_session, AlbumModel};
+
+    fn model(title: &str, artist: &str, album: Option<&str>) -> SessionModel {
+        let mut model = test_session(title, artist);
+        model.media.as_mut().unwrap().album = album.map(|a| AlbumModel {
+            artist: artist.to_string(),
+            title: a.to_string(),
+            track_count: 0,
+        });
+        model
+    }
+
+    fn render(source: &str, model: &SessionModel) -> String {
+        Template::parse(source)
+            .unwrap()
+            .render(&TemplateContext::new(model, None))
+    }
+
+    #[test]
+    fn renders_fields_and_filters() {
+        let model = model("Song", "Band", None);
+        assert_eq!(render("{artist} - {title | upper}", &model), "Band - SONG");
+        assert_eq!(render("{album | default(\"none\")}", &model), "none");
+        assert_eq!(render("{title | truncate(3)}", &model), "So…");
+    }
+
+    #[test]
+    fn picks_the_branch_of_a_condition() {
+        let source = "{title}{if album} [{album}]{else} (single){end}";
+        assert_eq!(
+            render(source, &model("Song", "Band", Some("LP"))),
+            "Song [LP]"
+        );
+        assert_eq!(
+            render(source, &model("Song", "Band", None)),
+            "Song (single)"
+        );
+
+        let negated = "{if !artist}unknown{end}";
+        assert_eq!(render(negated, &model("Song", "", None)), "unknown");
+        assert_eq!(render(negated, &model("Song", "Band", None)), "");
+
+        let compared = "{if status == \"playing\"}>{end}{if status != \"Playing\"}||{end}";
+        assert_eq!(render(compared, &model("Song", "Band", None)), ">");
+    }
+
+    #[test]
+    fn nests_conditions() {
+        let source = "{if artist}{if album}{album}{else}{artist}{end}{end}";
+        assert_eq!(render(source, &model("Song", "Band", Some("LP"))), "LP");
+        assert_eq!(render(source, &model("Song", "Band", None)), "Band");
+        assert_eq!(render(source, &model("Song", "", Some("LP"))), "");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let model = model("Song", "Band", None);
+        assert_eq!(render("{{{title}}}", &model), "{Song}");
+        assert_eq!(render("{{title}}", &model), "{title}");
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let invalid = [
+            "{title",
+            "title}",
+            "{nope}",
+            "{title | shout}",
+            "{title | truncate(0)}",
+            "{if title}",
+            "{else}",
+            "{end}",
+            "{if title}{else}{else}{end}",
+        ];
+        for source in invalid {
+            assert!(Template::parse(source).is_err(), "{source:?} parsed");
+        }
+    }
+}
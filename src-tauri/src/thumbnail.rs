@@ -0,0 +1,149 @@
+use std::io::Cursor;
+
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage, GenericImageView, ImageFormat,
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::MediaError,
+    model::{Palette, Thumbnail},
+    palette::extract_palette,
+};
+
+pub const MINI_SIZE: u32 = 50;
+pub const NORMAL_SIZE: u32 = 300;
+// the largest size a thumbnail can be set to, art is rarely bigger than this
+pub const MAX_SIZE: u32 = 1024;
+
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ThumbnailFit {
+    // cuts the longer side down to a square
+    #[default]
+    Crop,
+    // keeps the whole image and pads it with transparent bars
+    Letterbox,
+}
+
+// the [thumbnails] table of the settings file, sizes are in physical pixels
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ThumbnailOptions {
+    // shown in the mini bar
+    pub mini_size: u32,
+    // shown in normal mode and served when no size is asked for
+    pub normal_size: u32,
+    pub fit: ThumbnailFit,
+}
+
+impl Default for ThumbnailOptions {
+    fn default() -> Self {
+        Self {
+            mini_size: MINI_SIZE,
+            normal_size: NORMAL_SIZE,
+            fit: ThumbnailFit::default(),
+        }
+    }
+}
+
+impl ThumbnailOptions {
+    // every size rendered, smallest first
+    pub fn sizes(&self) -> Vec<u32> {
+        let mut sizes = vec![self.mini_size, self.normal_size];
+        sizes.retain(|&s| s > 0);
+        sizes.sort_unstable();
+        sizes.dedup();
+        sizes
+    }
+}
+
+#[derive(Debug)]
+pub struct ProcessedArt {
+    pub thumbnails: Vec<Thumbnail>,
+    pub palette: Option<Palette>,
+}
+
+pub fn detect_format(data: &[u8]) -> Result<ImageFormat, MediaError> {
+    match image::guess_format(data) {
+        Ok(f) => Ok(f),
+        Err(err) => Err(MediaError::thumbnail_failed("Unknown thumbnail format.").with_source(err)),
+    }
+}
+
+fn square(image: &DynamicImage, size: u32, fit: ThumbnailFit) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    match fit {
+        ThumbnailFit::Crop => {
+            let side = width.min(height);
+            image
+                .crop_imm((width - side) / 2, (height - side) / 2, side, side)
+                .resize_exact(size, size, FilterType::Lanczos3)
+        }
+        ThumbnailFit::Letterbox => {
+            let resized = image.resize(size, size, FilterType::Lanczos3);
+            let mut canvas = RgbaImage::new(size, size);
+            let x = (size - resized.width()) / 2;
+            let y = (size - resized.height()) / 2;
+            image::imageops::overlay(&mut canvas, &resized, x as i64, y as i64);
+            DynamicImage::ImageRgba8(canvas)
+        }
+    }
+}
+
+// JPEG keeps the payload small, PNG is only used when there is transparency to preserve
+fn encode(image: &DynamicImage) -> Result<(Vec<u8>, ImageFormat), MediaError> {
+    let mut data = Vec::new();
+    let result = if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .map(|_| ImageFormat::Png)
+    } else {
+        JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+            .encode_image(&image.to_rgb8())
+            .map(|_| ImageFormat::Jpeg)
+    };
+    match result {
+        Ok(format) => Ok((data, format)),
+        Err(err) => {
+            Err(MediaError::thumbnail_failed("Failed to encode thumbnail.").with_source(err))
+        }
+    }
+}
+
+pub fn process_thumbnail(
+    data: &[u8],
+    options: &ThumbnailOptions,
+) -> Result<ProcessedArt, MediaError> {
+    let format = detect_format(data)?;
+    let image = match image::load_from_memory_with_format(data, format) {
+        Ok(i) => i,
+        Err(err) => {
+            return Err(MediaError::thumbnail_failed(format!(
+                "Failed to decode {} thumbnail.",
+                format.to_mime_type()
+            ))
+            .with_source(err));
+        }
+    };
+    if image.width() == 0 || image.height() == 0 {
+        return Err(MediaError::thumbnail_failed("Thumbnail is empty."));
+    }
+
+    let sizes = options.sizes();
+    let mut thumbnails = Vec::with_capacity(sizes.len());
+    for size in sizes {
+        let (data, format) = encode(&square(&image, size, options.fit))?;
+        thumbnails.push(Thumbnail {
+            size,
+            mime_type: format.to_mime_type().to_string(),
+            data,
+        });
+    }
+    Ok(ProcessedArt {
+        thumbnails,
+        palette: extract_palette(&image),
+    })
+}
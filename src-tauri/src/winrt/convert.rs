@@ -1,263 +1,120 @@
-use gsmtc::{AlbumModel, MediaModel, PlaybackModel, TimelineModel};
-use windows::Media::{
-    Control::{
-        GlobalSystemMediaTransportControlsSessionMediaProperties,
-        GlobalSystemMediaTransportControlsSessionPlaybackInfo,
-        GlobalSystemMediaTransportControlsSessionPlaybackStatus,
-        GlobalSystemMediaTransportControlsSessionTimelineProperties,
-    },
-    MediaPlaybackAutoRepeatMode, MediaPlaybackType,
+use windows::Media::MediaPlaybackAutoRepeatMode;
+
+use crate::model::{
+    AlbumModel, AutoRepeatMode, MediaModel, PlaybackModel, PlaybackStatus, PlaybackType,
+    SessionModel, TimelineModel,
 };
 
-use super::error::ConvertError;
+// gsmtc emits its own models, so they are mapped into ours before leaving the backend
 
-pub fn convert_playback_info(
-    playback_info: &GlobalSystemMediaTransportControlsSessionPlaybackInfo,
-) -> Result<PlaybackModel, ConvertError> {
-    let status = match playback_info.PlaybackStatus() {
-        Ok(p) => match p {
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Changing => {
-                gsmtc::PlaybackStatus::Changing
-            }
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Closed => {
-                gsmtc::PlaybackStatus::Closed
-            }
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Opened => {
-                gsmtc::PlaybackStatus::Opened
-            }
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Paused => {
-                gsmtc::PlaybackStatus::Paused
-            }
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing => {
-                gsmtc::PlaybackStatus::Playing
-            }
-            GlobalSystemMediaTransportControlsSessionPlaybackStatus::Stopped => {
-                gsmtc::PlaybackStatus::Stopped
-            }
-            _ => {
-                return Err(ConvertError {
-                    message: "Unknown playback status.".to_string(),
-                });
-            }
-        },
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get playback status.".to_string(),
-            });
-        }
-    };
-    let playback_type = match playback_info.PlaybackType() {
-        Ok(i) => match i.Value() {
-            Ok(t) => match t {
-                MediaPlaybackType::Image => gsmtc::PlaybackType::Image,
-                MediaPlaybackType::Music => gsmtc::PlaybackType::Music,
-                MediaPlaybackType::Unknown => gsmtc::PlaybackType::Unknown,
-                MediaPlaybackType::Video => gsmtc::PlaybackType::Video,
-                _ => {
-                    return Err(ConvertError {
-                        message: "Unknown playback type.".to_string(),
-                    });
-                }
-            },
-            Err(_) => {
-                return Err(ConvertError {
-                    message: "Failed to get playback type.".to_string(),
-                });
-            }
-        },
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Faield to get playback type.".to_string(),
-            });
+impl From<gsmtc::SessionModel> for SessionModel {
+    fn from(model: gsmtc::SessionModel) -> Self {
+        Self {
+            playback: model.playback.map(Into::into),
+            timeline: model.timeline.map(Into::into),
+            media: model.media.map(Into::into),
+            source: model.source,
         }
-    };
-    let auto_repeat = match playback_info.AutoRepeatMode() {
-        Ok(i) => match i.Value() {
-            Ok(r) => match r {
-                MediaPlaybackAutoRepeatMode::List => Some(gsmtc::AutoRepeatMode::List),
-                MediaPlaybackAutoRepeatMode::None => Some(gsmtc::AutoRepeatMode::None),
-                MediaPlaybackAutoRepeatMode::Track => Some(gsmtc::AutoRepeatMode::Track),
-                _ => None,
-            },
-            Err(_) => None,
-        },
-        Err(_) => None,
-    };
-    let rate = match playback_info.PlaybackRate() {
-        Ok(i) => match i.Value() {
-            Ok(r) => Some(r),
-            Err(_) => None,
-        },
-        Err(_) => None,
-    };
-    let shuffle = match playback_info.IsShuffleActive() {
-        Ok(i) => match i.Value() {
-            Ok(s) => Some(s),
-            Err(_) => None,
-        },
-        Err(_) => None,
-    };
-    let playback = PlaybackModel {
-        status,
-        r#type: playback_type,
-        rate: rate.unwrap_or_default(),
-        shuffle: shuffle.unwrap_or_default(),
-        auto_repeat: auto_repeat.unwrap_or_default(),
-    };
-    Ok(playback)
+    }
 }
 
-pub fn convert_timeline_info(
-    timeline_info: &GlobalSystemMediaTransportControlsSessionTimelineProperties,
-) -> Result<TimelineModel, ConvertError> {
-    let start = match timeline_info.StartTime() {
-        Ok(s) => s.Duration,
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get start time.".to_string(),
-            });
-        }
-    };
-    let end = match timeline_info.EndTime() {
-        Ok(s) => s.Duration,
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get end time.".to_string(),
-            });
-        }
-    };
-    let position = match timeline_info.Position() {
-        Ok(s) => s.Duration,
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get position.".to_string(),
-            });
-        }
-    };
-    let last_updated_at_ms = match timeline_info.LastUpdatedTime() {
-        Ok(s) => s.UniversalTime,
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get last updated time.".to_string(),
-            });
+impl From<gsmtc::PlaybackModel> for PlaybackModel {
+    fn from(model: gsmtc::PlaybackModel) -> Self {
+        Self {
+            status: model.status.into(),
+            r#type: model.r#type.into(),
+            rate: model.rate,
+            shuffle: model.shuffle,
+            auto_repeat: model.auto_repeat.into(),
         }
-    };
-    let timeline = TimelineModel {
-        start,
-        end,
-        position,
-        last_updated_at_ms,
-    };
-    Ok(timeline)
+    }
 }
 
-pub fn convert_album_info(
-    album_info: &GlobalSystemMediaTransportControlsSessionMediaProperties,
-) -> Result<AlbumModel, ConvertError> {
-    let album_artist = match album_info.AlbumArtist() {
-        Ok(a) => a.to_string(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get album artist.".to_string(),
-            });
+impl From<gsmtc::TimelineModel> for TimelineModel {
+    fn from(model: gsmtc::TimelineModel) -> Self {
+        Self {
+            start: model.start,
+            end: model.end,
+            position: model.position,
+            last_updated_at_ms: model.last_updated_at_ms,
         }
-    };
-    let album_title = match album_info.AlbumTitle() {
-        Ok(a) => a.to_string(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get album title.".to_string(),
-            });
-        }
-    };
-    let album_track_count = match album_info.AlbumTrackCount() {
-        Ok(a) => a.unsigned_abs(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get album track count.".to_string(),
-            });
-        }
-    };
-    let album = AlbumModel {
-        artist: album_artist,
-        title: album_title,
-        track_count: album_track_count,
-    };
-    Ok(album)
+    }
 }
 
-pub fn convert_media_info(
-    media_info: &GlobalSystemMediaTransportControlsSessionMediaProperties,
-) -> Result<MediaModel, ConvertError> {
-    let album = match convert_album_info(&media_info) {
-        Ok(a) => Some(a),
-        Err(_) => None,
-    };
+impl From<gsmtc::MediaModel> for MediaModel {
+    fn from(model: gsmtc::MediaModel) -> Self {
+        Self {
+            title: model.title,
+            subtitle: model.subtitle,
+            artist: model.artist,
+            album: model.album.map(Into::into),
+            track_number: model.track_number,
+            genres: model.genres,
+            playback_type: model.playback_type.into(),
+        }
+    }
+}
 
-    let media_playback_type = match media_info.PlaybackType() {
-        Ok(i) => match i.Value() {
-            Ok(p) => match p {
-                MediaPlaybackType::Image => gsmtc::PlaybackType::Image,
-                MediaPlaybackType::Music => gsmtc::PlaybackType::Music,
-                MediaPlaybackType::Unknown => gsmtc::PlaybackType::Unknown,
-                MediaPlaybackType::Video => gsmtc::PlaybackType::Video,
-                _ => {
-                    return Err(ConvertError {
-                        message: "Unknown playback type.".to_string(),
-                    });
-                }
-            },
-            Err(_) => {
-                return Err(ConvertError {
-                    message: "Failed to get playback type.".to_string(),
-                });
-            }
-        },
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get playback type.".to_string(),
-            });
+impl From<gsmtc::AlbumModel> for AlbumModel {
+    fn from(model: gsmtc::AlbumModel) -> Self {
+        Self {
+            artist: model.artist,
+            title: model.title,
+            track_count: model.track_count,
         }
-    };
-    let title = match media_info.Title() {
-        Ok(t) => t.to_string(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get title.".to_string(),
-            });
+    }
+}
+
+impl From<gsmtc::PlaybackStatus> for PlaybackStatus {
+    fn from(status: gsmtc::PlaybackStatus) -> Self {
+        match status {
+            gsmtc::PlaybackStatus::Closed => PlaybackStatus::Closed,
+            gsmtc::PlaybackStatus::Opened => PlaybackStatus::Opened,
+            gsmtc::PlaybackStatus::Changing => PlaybackStatus::Changing,
+            gsmtc::PlaybackStatus::Stopped => PlaybackStatus::Stopped,
+            gsmtc::PlaybackStatus::Playing => PlaybackStatus::Playing,
+            gsmtc::PlaybackStatus::Paused => PlaybackStatus::Paused,
         }
-    };
-    let subtitle = match media_info.Subtitle() {
-        Ok(t) => t.to_string(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get subtitle.".to_string(),
-            });
+    }
+}
+
+impl From<gsmtc::PlaybackType> for PlaybackType {
+    fn from(playback_type: gsmtc::PlaybackType) -> Self {
+        match playback_type {
+            gsmtc::PlaybackType::Unknown => PlaybackType::Unknown,
+            gsmtc::PlaybackType::Music => PlaybackType::Music,
+            gsmtc::PlaybackType::Video => PlaybackType::Video,
+            gsmtc::PlaybackType::Image => PlaybackType::Image,
         }
-    };
-    let artist = match media_info.Artist() {
-        Ok(t) => t.to_string(),
-        Err(_) => {
-            return Err(ConvertError {
-                message: "Failed to get artist.".to_string(),
-            });
+    }
+}
+
+impl From<gsmtc::AutoRepeatMode> for AutoRepeatMode {
+    fn from(mode: gsmtc::AutoRepeatMode) -> Self {
+        match mode {
+            gsmtc::AutoRepeatMode::None => AutoRepeatMode::None,
+            gsmtc::AutoRepeatMode::Track => AutoRepeatMode::Track,
+            gsmtc::AutoRepeatMode::List => AutoRepeatMode::List,
         }
-    };
+    }
+}
 
-    let media = MediaModel {
-        title,
-        subtitle,
-        artist,
-        album,
-        track_number: None, // we do not use this value as of now
-        genres: media_info
-            .Genres()
-            .unwrap()
-            .into_iter()
-            .map(|s| s.to_string())
-            .collect(),
-        playback_type: media_playback_type,
-    };
+impl From<MediaPlaybackAutoRepeatMode> for AutoRepeatMode {
+    fn from(mode: MediaPlaybackAutoRepeatMode) -> Self {
+        match mode {
+            MediaPlaybackAutoRepeatMode::Track => AutoRepeatMode::Track,
+            MediaPlaybackAutoRepeatMode::List => AutoRepeatMode::List,
+            _ => AutoRepeatMode::None,
+        }
+    }
+}
 
-    Ok(media)
+impl From<AutoRepeatMode> for MediaPlaybackAutoRepeatMode {
+    fn from(mode: AutoRepeatMode) -> Self {
+        match mode {
+            AutoRepeatMode::None => MediaPlaybackAutoRepeatMode::None,
+            AutoRepeatMode::Track => MediaPlaybackAutoRepeatMode::Track,
+            AutoRepeatMode::List => MediaPlaybackAutoRepeatMode::List,
+        }
+    }
 }
@@ -1,297 +1,204 @@
-use gsmtc::{ManagerEvent::*, SessionModel, SessionUpdateEvent::*};
-use tauri::AppHandle;
-use windows::{
-    Media::Control::{
-        GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
-    },
-    Storage::Streams::{DataReader, IRandomAccessStreamWithContentType},
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex as SyncMutex},
 };
 
-use crate::{emit_event, winrt::model::SessionUpdate};
+use gsmtc::{ManagerEvent::*, SessionUpdateEvent::*};
+use tauri::async_runtime::{channel, Receiver, Sender};
+use windows::Media::Control::{
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
+};
 
-use super::{
-    convert::{convert_media_info, convert_playback_info, convert_timeline_info},
-    error::WinRTError,
+use crate::{
+    backend::{BackendEvent, MediaBackend},
+    error::MediaError,
     model::{
-        ActiveSessionChange, ActiveSessionRemove, CurrentSession, NowPlaying, SessionControl,
-        SessionCreate, SessionRemove,
+        ActiveSessionChange, AutoRepeatMode, SessionControl, SessionCreate, SessionRemove,
+        SessionUpdate,
     },
 };
 
-pub struct MediaClient {
+// what controls sent to an id the event task announced end up at
+#[derive(Clone)]
+enum ControlTarget {
+    Session(GlobalSystemMediaTransportControlsSession),
+    // gsmtc does not hand out the session behind its ids, only the source. while the manager
+    // lists more than one session for that source there is no telling which one the id is, and
+    // a control could reach the wrong player, so it is refused instead
+    Ambiguous { source: String, count: usize },
+}
+
+pub struct WinRTBackend {
     pub session_manager: GlobalSystemMediaTransportControlsSessionManager,
-    // pub handle: AppHandle,
-    pub current_sessions: Vec<NowPlaying>,
+    sessions: Arc<SyncMutex<BTreeMap<usize, ControlTarget>>>,
 }
 
-impl MediaClient {
-    pub fn new() -> Result<Self, WinRTError> {
+impl WinRTBackend {
+    pub fn new() -> Result<Self, MediaError> {
         let session_manager = match GlobalSystemMediaTransportControlsSessionManager::RequestAsync()
         {
             Ok(i) => match i.get() {
                 Ok(m) => m,
-                Err(_) => {
-                    return Err(WinRTError {
-                        message: "Failed to get session manager.".to_string(),
-                    });
+                Err(err) => {
+                    return Err(
+                        MediaError::backend_unavailable("Failed to get session manager.")
+                            .with_source(err),
+                    );
                 }
             },
-            Err(_) => {
-                return Err(WinRTError {
-                    message: "Failed to request session manager.".to_string(),
-                });
+            Err(err) => {
+                return Err(
+                    MediaError::backend_unavailable("Failed to request session manager.")
+                        .with_source(err),
+                );
             }
         };
-        let current_sessions = Vec::<NowPlaying>::new();
         Ok(Self {
             session_manager,
-            current_sessions,
+            sessions: Arc::new(SyncMutex::new(BTreeMap::new())),
         })
     }
 
-    /*
-    pub fn init_sessions_handler(&self) -> Result<(), WinRTError> {
-        let handler = TypedEventHandler::<
-            GlobalSystemMediaTransportControlsSessionManager,
-            SessionsChangedEventArgs,
-        >::new(|sm, _| {
-            // this event can detect media play/stop (not resume/pause)
-            let new_sessions = match sm.clone().unwrap().GetSessions() {
-                Ok(n) => n,
-                Err(err) => {
-                    println!("Failed to get new sessions.\n{}", err.to_string());
-                    return Ok(());
-                }
-            };
-            for new_session in new_sessions {}
-            Ok(())
-        });
-        if let Err(err) = self.session_manager.SessionsChanged(&handler) {
-            return Err(WinRTError {
-                message: format!(
-                    "Failed to add event handler to sessions manager.\n{}",
-                    err.to_string()
-                ),
-            });
+    // the session behind an id is the one the manager lists for its source, as long as there is
+    // only one. most players only ever open one session, browsers open one per tab
+    fn target_for_source(
+        session_manager: &GlobalSystemMediaTransportControlsSessionManager,
+        source: &str,
+    ) -> Option<ControlTarget> {
+        let sessions = match session_manager.GetSessions() {
+            Ok(s) => s,
+            Err(err) => {
+                println!("Failed to get current sessions.\n{}", err);
+                return None;
+            }
+        };
+        let mut matching = sessions
+            .into_iter()
+            .filter(|s| {
+                s.SourceAppUserModelId()
+                    .is_ok_and(|src| src.to_string() == source)
+            })
+            .collect::<Vec<_>>();
+        match matching.len() {
+            0 => None,
+            1 => matching.pop().map(ControlTarget::Session),
+            count => Some(ControlTarget::Ambiguous {
+                source: source.to_string(),
+                count,
+            }),
         }
-        Ok(())
     }
-    */
 
-    pub async fn init_event_handler(
-        handle: &AppHandle,
-    ) -> Result<(), Box<dyn std::error::Error + 'static>> {
-        let mut rx = gsmtc::SessionManager::create().await.unwrap();
+    async fn forward_events(
+        session_manager: GlobalSystemMediaTransportControlsSessionManager,
+        sessions: Arc<SyncMutex<BTreeMap<usize, ControlTarget>>>,
+        tx: Sender<BackendEvent>,
+    ) {
+        let mut rx = match gsmtc::SessionManager::create().await {
+            Ok(rx) => rx,
+            Err(err) => {
+                println!("Failed to create gsmtc session manager.\n{}", err);
+                return;
+            }
+        };
         while let Some(evt) = rx.recv().await {
-            match evt {
+            let event = match evt {
                 SessionCreated {
                     session_id,
                     mut rx,
                     source,
                 } => {
-                    let app_handle = handle.clone();
-                    emit_event(
-                        "session_create",
-                        SessionCreate {
-                            session_id: session_id.clone(),
-                            source: source.clone(),
-                        },
-                        &app_handle,
-                    );
+                    match WinRTBackend::target_for_source(&session_manager, &source) {
+                        Some(target) => {
+                            sessions.lock().unwrap().insert(session_id, target);
+                        }
+                        None => println!(
+                            "[{session_id}/{source}] session not found, controls will fail"
+                        ),
+                    }
+                    let session_tx = tx.clone();
                     tauri::async_runtime::spawn(async move {
                         while let Some(evt) = rx.recv().await {
-                            match evt {
-                                Model(model) => {
-                                    emit_event(
-                                        "session_update",
-                                        SessionUpdate {
-                                            session_id,
-                                            source: source.clone(),
-                                            session_model: model,
-                                            image: None,
-                                        },
-                                        &app_handle,
-                                    );
-                                }
-                                Media(model, image) => {
-                                    if let Some(img) = image {
-                                        emit_event(
-                                            "session_update",
-                                            SessionUpdate {
-                                                session_id,
-                                                source: source.clone(),
-                                                session_model: model,
-                                                image: Some(img.data),
-                                            },
-                                            &app_handle,
-                                        );
-                                    } else {
-                                        emit_event(
-                                            "session_update",
-                                            SessionUpdate {
-                                                session_id,
-                                                source: source.clone(),
-                                                session_model: model,
-                                                image: None,
-                                            },
-                                            &app_handle,
-                                        );
-                                    }
-                                }
+                            let update = match evt {
+                                Model(model) => SessionUpdate {
+                                    session_id,
+                                    source: source.clone(),
+                                    session_model: model.into(),
+                                    image: None,
+                                    art_id: None,
+                                    palette: None,
+                                },
+                                Media(model, image) => SessionUpdate {
+                                    session_id,
+                                    source: source.clone(),
+                                    session_model: model.into(),
+                                    image: image.map(|img| img.data),
+                                    art_id: None,
+                                    palette: None,
+                                },
+                            };
+                            if session_tx
+                                .send(BackendEvent::SessionUpdate(update))
+                                .await
+                                .is_err()
+                            {
+                                break;
                             }
                         }
                         println!("[{session_id}/{source}] exited event-loop");
                     });
+                    BackendEvent::SessionCreate(SessionCreate { session_id, source })
                 }
                 SessionRemoved { session_id } => {
-                    emit_event(
-                        "session_remove",
-                        SessionRemove {
-                            session_id: session_id.clone(),
-                        },
-                        handle,
-                    );
-                }
-                CurrentSessionChanged {
-                    session_id: Some(id),
-                } => {
-                    emit_event(
-                        "current_session_change",
-                        ActiveSessionChange {
-                            session_id: id.clone(),
-                        },
-                        handle,
-                    );
-                }
-                CurrentSessionChanged { session_id: None } => {
-                    emit_event("current_session_remove", ActiveSessionRemove, handle);
+                    sessions.lock().unwrap().remove(&session_id);
+                    BackendEvent::SessionRemove(SessionRemove { session_id })
                 }
+                CurrentSessionChanged { session_id } => BackendEvent::ActiveSessionChange(
+                    session_id.map(|id| ActiveSessionChange { session_id: id }),
+                ),
+            };
+            if tx.send(event).await.is_err() {
+                break;
             }
         }
-        Ok(())
     }
 
-    // I think we should not use this function except launch timing
-    pub fn get_current_sessions(&self) -> Result<Vec<CurrentSession>, WinRTError> {
-        let sessions = match self.session_manager.GetSessions() {
-            Ok(s) => s,
-            Err(_) => {
-                return Err(WinRTError {
-                    message: "Failed to get current sessions.".to_string(),
-                });
+    fn find_session(
+        &self,
+        session_id: usize,
+    ) -> Result<GlobalSystemMediaTransportControlsSession, MediaError> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let target = match sessions.get(&session_id) {
+            Some(ControlTarget::Ambiguous { source, .. }) => {
+                // the other sessions of the source may have closed since
+                WinRTBackend::target_for_source(&self.session_manager, source)
             }
+            target => target.cloned(),
         };
-        let mut current_sessions = Vec::<CurrentSession>::new();
-        for session in sessions {
-            let info = match session.TryGetMediaPropertiesAsync() {
-                Ok(a) => match a.get() {
-                    Ok(p) => p,
-                    Err(_) => {
-                        return Err(WinRTError {
-                            message: "Failed to get session info.".to_string(),
-                        });
-                    }
-                },
-                Err(_) => {
-                    return Err(WinRTError {
-                        message: "Failed to get session info.".to_string(),
-                    });
-                }
-            };
-            let thumbnail: Option<Vec<u8>> = match info.Thumbnail() {
-                Ok(t) => {
-                    let thumbnail_stream = t.OpenReadAsync().unwrap().get().unwrap();
-                    let thumbnail_vec = match MediaClient::decode_thumbnail(thumbnail_stream) {
-                        Ok(t) => t,
-                        Err(_) => {
-                            return Err(WinRTError {
-                                message: "Failed to decode thumbnail.".to_string(),
-                            });
-                        }
-                    };
-                    Some(thumbnail_vec)
-                }
-                Err(_) => {
-                    println!("There might be no thumbnail for this content.");
-                    None
-                }
-            };
-            let source = session.SourceAppUserModelId().unwrap(); // execution file name (e.g. Spotify.exe)
-
-            // playback
-            let playback_info = match session.GetPlaybackInfo() {
-                Ok(p) => p,
-                Err(_) => {
-                    return Err(WinRTError {
-                        message: "Failed to get playback info.".to_string(),
-                    });
-                }
-            };
-            let playback = match convert_playback_info(&playback_info) {
-                Ok(p) => Some(p),
-                Err(err) => {
-                    println!("{}", err.message);
-                    None
-                }
-            };
-
-            // timeline
-            let timeline_info = match session.GetTimelineProperties() {
-                Ok(t) => t,
-                Err(_) => {
-                    return Err(WinRTError {
-                        message: "Failed to get timeline info.".to_string(),
-                    });
-                }
-            };
-            let timeline = match convert_timeline_info(&timeline_info) {
-                Ok(t) => Some(t),
-                Err(_) => None,
-            };
-
-            // media
-            let media = match convert_media_info(&info) {
-                Ok(m) => Some(m),
-                Err(_) => None,
-            };
-
-            let session = SessionModel {
-                playback,
-                timeline,
-                media,
-                source: source.clone().to_string(),
-            };
-
-            let data = CurrentSession {
-                source: source.to_string(),
-                session,
-                image: thumbnail,
-            };
-            current_sessions.push(data);
+        match target {
+            Some(ControlTarget::Session(session)) => {
+                sessions.insert(session_id, ControlTarget::Session(session.clone()));
+                Ok(session)
+            }
+            Some(ControlTarget::Ambiguous { source, count }) => {
+                Err(MediaError::control_rejected(format!(
+                    "Session {session_id} can not be controlled, {source} has {count} sessions \
+                     open and there is no telling which one it is."
+                ))
+                .with_session(session_id))
+            }
+            None => Err(
+                MediaError::not_found(format!("Session {session_id} no longer exists."))
+                    .with_session(session_id),
+            ),
         }
-        Ok(current_sessions)
-    }
-
-    pub fn decode_thumbnail(
-        stream: IRandomAccessStreamWithContentType,
-    ) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
-        let stream_len = stream.Size().unwrap() as usize;
-        let mut data = vec![0u8; stream_len];
-        let reader = DataReader::CreateDataReader(&stream).unwrap();
-        reader.LoadAsync(stream_len as u32).unwrap().get().unwrap();
-        reader.ReadBytes(&mut data).unwrap();
-
-        reader.Close().ok();
-        stream.Close().ok();
-
-        Ok(data)
     }
 
     pub fn control(
         &self,
         session: &GlobalSystemMediaTransportControlsSession,
         control: &SessionControl,
-    ) -> Result<bool, Box<dyn std::error::Error>> {
+    ) -> windows::core::Result<bool> {
         let result = match control {
             SessionControl::Play => session.TryPlayAsync(),
             SessionControl::Pause => session.TryPauseAsync(),
@@ -300,69 +207,58 @@ impl MediaClient {
             SessionControl::Rewind => session.TryRewindAsync(),
             SessionControl::SkipNext => session.TrySkipNextAsync(),
             SessionControl::SkipPrevious => session.TrySkipPreviousAsync(),
+            SessionControl::SeekTo(position) => session.TryChangePlaybackPositionAsync(*position),
+            SessionControl::SeekBy(offset) => {
+                let position = session.GetTimelineProperties()?.Position()?.Duration;
+                session.TryChangePlaybackPositionAsync(position + offset)
+            }
+            SessionControl::ToggleShuffle => {
+                let shuffle = session.GetPlaybackInfo()?.IsShuffleActive()?.Value()?;
+                session.TryChangeShuffleActiveAsync(!shuffle)
+            }
+            SessionControl::SetShuffle(shuffle) => session.TryChangeShuffleActiveAsync(*shuffle),
+            SessionControl::CycleRepeat => {
+                let mode = session.GetPlaybackInfo()?.AutoRepeatMode()?.Value()?;
+                let mode = AutoRepeatMode::from(mode).cycle();
+                session.TryChangeAutoRepeatModeAsync(mode.into())
+            }
+            SessionControl::SetRepeat(mode) => session.TryChangeAutoRepeatModeAsync((*mode).into()),
+            SessionControl::SetRate(rate) => session.TryChangePlaybackRateAsync(*rate),
         };
-        return Ok(result?.get()?);
+        result?.get()
     }
+}
 
-    pub fn control_session(
+impl MediaBackend for WinRTBackend {
+    fn control_session(
         &self,
-        source: String,
+        session_id: usize,
         control: SessionControl,
-    ) -> Result<(), WinRTError> {
-        let sessions = match self.session_manager.GetSessions() {
-            Ok(s) => s,
-            Err(_) => {
-                return Err(WinRTError {
-                    message: "Failed to get current sessions.".to_string(),
-                });
-            }
-        };
-        for session in sessions {
-            let Ok(src) = session.SourceAppUserModelId() else {
-                continue;
-            };
-            if src.to_string() == source {
-                match self.control(&session, &control) {
-                    Ok(res) => match res {
-                        true => return Ok(()),
-                        false => {
-                            return Err(WinRTError {
-                                message: format!(
-                                    "Failed to control {source} session: {:?}",
-                                    control
-                                ),
-                            })
-                        }
-                    },
-                    Err(_) => {
-                        return Err(WinRTError {
-                            message: format!("Failed to control {source} session: {:?}", control),
-                        })
-                    }
-                }
-            }
+    ) -> Result<(), MediaError> {
+        let session = self.find_session(session_id)?;
+        match self.control(&session, &control) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(MediaError::control_rejected(format!(
+                "Failed to control session {session_id}: {:?}",
+                control
+            ))
+            .with_session(session_id)),
+            Err(err) => Err(MediaError::control_rejected(format!(
+                "Failed to control session {session_id}: {:?}",
+                control
+            ))
+            .with_session(session_id)
+            .with_source(err)),
         }
-        Err(WinRTError {
-            message: "Failed to find session.".to_string(),
-        })
     }
 
-    /*
-    pub fn update_session(mut self, session: NowPlaying) -> Result<(), WinRTError> {
-        // this function updates the current state of one of the current_sessions
-        // should be called in each session's event handler
-        let prev_length = self.current_sessions.len();
-        let new_sessions_iterator = self
-            .current_sessions
-            .into_iter()
-            .filter(|s| s.guid != session.guid);
-        let mut new_sessions = new_sessions_iterator.collect::<Vec<NowPlaying>>();
-        let new_length = new_sessions.len();
-        if prev_length != new_length {
-            new_sessions.push(session);
-        }
-        self.current_sessions = new_sessions;
-        Ok(())
+    fn subscribe(&self) -> Result<Receiver<BackendEvent>, MediaError> {
+        let (tx, rx) = channel(64);
+        tauri::async_runtime::spawn(WinRTBackend::forward_events(
+            self.session_manager.clone(),
+            Arc::clone(&self.sessions),
+            tx,
+        ));
+        Ok(rx)
     }
-    */
 }
@@ -1,4 +1,2 @@
 pub mod convert;
-pub mod error;
 pub mod media;
-pub mod model;
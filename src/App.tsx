@@ -1,5 +1,5 @@
 import { useEffect, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { convertFileSrc, invoke } from '@tauri-apps/api/core';
 import './App.css';
 import {
   ActiveSessionChange,
@@ -8,39 +8,62 @@ import {
   SessionCreate,
   SessionRemove,
   SessionUpdate,
-  WinRTError,
+  MediaError,
+  Settings,
+  ThumbnailOptions,
 } from './types/winrt';
 import { listen, UnlistenFn } from '@tauri-apps/api/event';
 import { debugPrint } from './utils/debug';
 import NormalMode from './components/NormalMode';
 import MiniMode from './components/MiniMode';
 
+// served by the nowplaying protocol, see protocol.rs
+const createArtUrl = (artId: string, size: number) => convertFileSrc(`${artId}/${size}`, 'nowplaying');
+
+// at the sizes the backend renders thumbnails at, which come with the settings
+const withArtUrls = (session: Session, thumbnails: ThumbnailOptions): Session => {
+  if (!session.artId) return session;
+  return {
+    ...session,
+    imageUrl: createArtUrl(session.artId, thumbnails.normalSize),
+    miniImageUrl: createArtUrl(session.artId, thumbnails.miniSize),
+  };
+};
+
+const bySource = (a: Session, b: Session) => (a.source > b.source ? 1 : -1);
+
 function App() {
   const [sessions, setSessions] = useState<Session[]>([]);
+  // the session the system reports as current, null while there is none
+  const [activeSessionId, setActiveSessionId] = useState<number | null>(null);
   const [isMini, setIsMini] = useState(false);
+  const [settings, setSettings] = useState<Settings | null>(null);
 
-  const controlSession = (source: string, control: SessionControl) => {
-    invoke('control_session', { source, control })
+  const controlSession = (sessionId: number, control: SessionControl) => {
+    invoke('control_session', { sessionId, control })
       .then(() => {
         debugPrint('Session control succeeded');
       })
       .catch((e) => {
-        const err = e as WinRTError;
-        debugPrint(`Failed to control: ${err.message}, ${source}:${control}`);
+        const err = e as MediaError;
+        debugPrint(`Failed to control: ${err.message}, ${sessionId}:${JSON.stringify(control)}`);
+      });
+  };
+
+  const setAlwaysOnTop = (alwaysOnTop: boolean) => {
+    if (settings === null) return;
+    // the backend applies it to the window and answers with settings_changed
+    invoke<Settings>('update_settings', { settings: { ...settings, window: { ...settings.window, alwaysOnTop } } })
+      .then(setSettings)
+      .catch((e) => {
+        const err = e as MediaError;
+        debugPrint(`Failed to update settings: ${err.message}`);
       });
   };
 
   const initSessions = () => {
     invoke<Session[]>('get_current_sessions').then((s) => {
-      const ss = s.sort((a, b) => {
-        if (a.source > b.source) return 1;
-        else return -1;
-      });
-      ss.forEach((session) => {
-        if (session.image) {
-          session.imageUrl = URL.createObjectURL(new Blob([new Uint8Array(session.image)], { type: 'image/png' }));
-        }
-      });
+      const ss = s.sort(bySource);
       console.log(ss);
       setSessions(ss);
     });
@@ -48,40 +71,37 @@ function App() {
 
   useEffect(() => {
     initSessions();
+    invoke<Settings>('get_settings')
+      .then(setSettings)
+      .catch((e) => {
+        const err = e as MediaError;
+        debugPrint(`Failed to get settings: ${err.message}`);
+      });
 
     const unlistenFuncs: UnlistenFn[] = [];
     const initListeners = async () => {
       const unlistenSessionCreateListener = await listen<SessionCreate>('session_create', (e) => {
         debugPrint('Session Create: ', e.payload);
         setSessions((prev) => {
-          return [...prev, { source: e.payload.source }];
+          if (prev.some((s) => s.sessionId === e.payload.sessionId)) return prev;
+          return [...prev, { sessionId: e.payload.sessionId, source: e.payload.source }].sort(bySource);
         });
       });
       unlistenFuncs.push(unlistenSessionCreateListener);
 
       const unlistenSessionUpdateListener = await listen<SessionUpdate>('session_update', (e) => {
         debugPrint('Session Update', e.payload);
-        setSessions((prev) => {
-          const exist = prev.find((s) => s.source === e.payload.source);
-          const filtered = prev.filter((s) => s.source !== e.payload.source);
-          if (exist === undefined) {
-            return prev;
-          } else {
-            // update info
-            if (e.payload.image) {
-              exist.image = e.payload.image;
-              if (exist.imageUrl) URL.revokeObjectURL(exist.imageUrl);
-              exist.imageUrl = URL.createObjectURL(new Blob([new Uint8Array(e.payload.image)], { type: 'image/png' }));
-            }
-            exist.session = e.payload.sessionModel;
-            exist.sessionId = e.payload.sessionId;
-            const newSessions = [...filtered, exist].sort((a, b) => {
-              if (a.source > b.source) return 1;
-              else return -1;
-            });
-            return newSessions;
-          }
-        });
+        // a new object for the changed session only, so that the others keep their identity
+        setSessions((prev) =>
+          prev.map((s) => {
+            if (s.sessionId !== e.payload.sessionId) return s;
+            const art =
+              e.payload.artId && e.payload.artId !== s.artId
+                ? { artId: e.payload.artId, palette: e.payload.palette }
+                : {};
+            return { ...s, ...art, session: e.payload.sessionModel };
+          }),
+        );
       });
       unlistenFuncs.push(unlistenSessionUpdateListener);
 
@@ -95,14 +115,14 @@ function App() {
 
       const unlistenCurrentSessionChangeListener = await listen<ActiveSessionChange>('current_session_change', (e) => {
         debugPrint('Current Session Change', e.payload);
-        // todo
+        setActiveSessionId(e.payload.sessionId);
       });
       unlistenFuncs.push(unlistenCurrentSessionChangeListener);
 
       const unlistenCurrentSessionRemoveListener = await listen('current_session_remove', () => {
         debugPrint('Current Session Remove');
-        // currently no sessions
-        setSessions([]);
+        // the other sessions are still there, only none of them is current
+        setActiveSessionId(null);
       });
       unlistenFuncs.push(unlistenCurrentSessionRemoveListener);
 
@@ -116,6 +136,12 @@ function App() {
         initSessions();
       });
       unlistenFuncs.push(unlistenMiniListener);
+
+      const unlistenSettingsListener = await listen<Settings>('settings_changed', (e) => {
+        debugPrint('Settings Changed', e.payload);
+        setSettings(e.payload);
+      });
+      unlistenFuncs.push(unlistenSettingsListener);
     };
 
     initListeners();
@@ -123,18 +149,28 @@ function App() {
       unlistenFuncs.forEach((f) => {
         f();
       });
-      sessions.forEach((s) => {
-        if (s.imageUrl) URL.revokeObjectURL(s.imageUrl);
-      });
     };
   }, []);
 
+  // the carousels open on their first item, which is the current session when there is one
+  const ordered = [
+    ...sessions.filter((s) => s.sessionId === activeSessionId),
+    ...sessions.filter((s) => s.sessionId !== activeSessionId),
+  ];
+  const thumbnails = settings?.thumbnails;
+  const shownSessions = thumbnails ? ordered.map((s) => withArtUrls(s, thumbnails)) : ordered;
+
   return (
     <div className={`w-[${window.innerWidth}px] h-[${window.innerHeight}px]`}>
       {isMini ? (
-        <MiniMode sessions={sessions} controlSession={controlSession} />
+        <MiniMode sessions={shownSessions} controlSession={controlSession} />
       ) : (
-        <NormalMode sessions={sessions} controlSession={controlSession} />
+        <NormalMode
+          sessions={shownSessions}
+          controlSession={controlSession}
+          alwaysOnTop={settings?.window.alwaysOnTop ?? false}
+          setAlwaysOnTop={setAlwaysOnTop}
+        />
       )}
     </div>
   );
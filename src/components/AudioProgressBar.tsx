@@ -1,51 +1,56 @@
-import type { PlaybackModel, Timeline, TimelineModel } from '@/types/winrt';
-import { useEffect, useState } from 'react';
-
-const AudioProgressBar = ({
-  rawTimeline,
-  playbackStatus,
-}: {
-  rawTimeline?: TimelineModel;
-  playbackStatus?: PlaybackModel['status'];
-}) => {
-  if (!rawTimeline || !playbackStatus) return <div></div>;
-  const [timeline, setTimeline] = useState<Timeline>({ positionMs: 0, length: 1 });
-  useEffect(() => {
-    const length = rawTimeline.end / 10000000;
-    const positionMs = rawTimeline.position / 10000;
-    clearInterval(timeline.positionLoop);
-    if (playbackStatus === 'Playing') {
-      const interval = setInterval(() => {
-        setTimeline((prev) => {
-          return { ...prev, positionMs: (prev.positionMs += 250) };
-        });
-      }, 250);
-      setTimeline({ length, positionMs, positionLoop: interval });
-    } else if (playbackStatus === 'Paused') {
-      setTimeline({ length, positionMs });
-    }
+import type { SessionPosition } from '@/types/winrt';
+import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
+import { useEffect, useState, type MouseEvent } from 'react';
+
+const TICKS_PER_SECOND = 10000000;
+
+const formatTime = (ticks: number) => {
+  const totalSecs = Math.max(Math.trunc(ticks / TICKS_PER_SECOND), 0);
+  const mins = Math.floor(totalSecs / 60);
+  const secs = totalSecs - mins * 60;
+  return `${mins}:${secs < 10 ? `0${secs}` : secs}`;
+};
+
+const AudioProgressBar = ({ sessionId, onSeek }: { sessionId: number; onSeek?: (position: number) => void }) => {
+  const [position, setPosition] = useState<SessionPosition | null>(null);
 
+  useEffect(() => {
+    // the backend keeps the clock, so this only mirrors what it sends
+    invoke<SessionPosition>('get_position', { sessionId })
+      .then(setPosition)
+      .catch(() => setPosition(null));
+    const unlisten = listen<SessionPosition>('session_position', (e) => {
+      if (e.payload.sessionId === sessionId) setPosition(e.payload);
+    });
     return () => {
-      clearInterval(timeline.positionLoop);
+      unlisten.then((f) => f());
     };
-  }, [rawTimeline]);
+  }, [sessionId]);
+
+  if (!position) return <div></div>;
+
+  const length = position.end - position.start;
+  const elapsed = position.position - position.start;
+  const percentage = length > 0 ? (elapsed / length) * 100 : 0;
+
+  const seek = (e: MouseEvent<HTMLDivElement>) => {
+    if (!onSeek || length <= 0) return;
+    const rect = e.currentTarget.getBoundingClientRect();
+    const ratio = Math.min(Math.max((e.clientX - rect.left) / rect.width, 0), 1);
+    onSeek(Math.round(position.start + ratio * length));
+  };
 
-  const posMins = Math.floor(timeline.positionMs / 60000);
-  const posSecs = Math.trunc((timeline.positionMs - posMins * 60000) / 1000);
-  const lenMins = Math.floor(timeline.length / 60);
-  const lenSecs = Math.trunc(timeline.length - lenMins * 60);
-  const percentage = (timeline.positionMs / 1000 / timeline.length) * 100;
   return (
     <div className="w-full">
       <div className="w-full flex justify-between text-xs text-gray-300">
-        <p>
-          {posMins}:{posSecs < 10 ? `0${posSecs}` : posSecs}
-        </p>
-        <p>
-          {lenMins}:{lenSecs < 10 ? `0${lenSecs}` : lenSecs}
-        </p>
+        <p>{formatTime(elapsed)}</p>
+        <p>{formatTime(length)}</p>
       </div>
-      <div className="w-full bg-gray-200 rounded-full h-1.5 mb-1 dark:bg-gray-700">
+      <div
+        className={`w-full bg-gray-200 rounded-full h-1.5 mb-1 dark:bg-gray-700 ${onSeek ? 'cursor-pointer' : ''}`}
+        onClick={seek}
+      >
         <div className="bg-white h-1.5 rounded-full dark:bg-white" style={{ width: `${percentage}%` }}></div>
       </div>
     </div>
@@ -7,7 +7,7 @@ import { SkipBack, Pause, Play, SkipForward } from 'lucide-react';
 
 type Props = {
   sessions: Session[];
-  controlSession: (source: string, control: SessionControl) => void;
+  controlSession: (sessionId: number, control: SessionControl) => void;
 };
 
 const MiniMode = ({ sessions, controlSession }: Props) => {
@@ -19,26 +19,26 @@ const MiniMode = ({ sessions, controlSession }: Props) => {
             <CarouselItem key={i}>
               <div className={`grid grid-cols-8 gap-3 w-[${window.innerWidth}px] h-[${window.innerHeight}px]`}>
                 <div className="col-span-1">
-                  <img className="h-full object-contain" src={s.imageUrl} />
+                  <img className="h-full object-contain" src={s.miniImageUrl} />
                 </div>
                 <div className="col-span-3 h-full">
                   <div className="grid grid-cols-3 grid-rows-2 gap-1">
                     <div className="col-span-4">
                       <OverflowingText text={`${s.session?.media?.title} - ${s.session?.media?.artist}`} twClass="" />
                     </div>
-                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'SkipPrevious')}>
+                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'SkipPrevious')}>
                       <SkipBack />
                     </Button>
-                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'TogglePlayPause')}>
+                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'TogglePlayPause')}>
                       {s.session?.playback?.status === 'Playing' ? <Pause /> : <Play />}
                     </Button>
-                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'SkipNext')}>
+                    <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'SkipNext')}>
                       <SkipForward />
                     </Button>
                   </div>
                 </div>
                 <div className="col-span-4 w-full">
-                  <AudioProgressBar rawTimeline={s.session?.timeline} playbackStatus={s.session?.playback?.status} />
+                  <AudioProgressBar sessionId={s.sessionId} />
                 </div>
               </div>
             </CarouselItem>
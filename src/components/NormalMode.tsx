@@ -1,30 +1,22 @@
 import { Carousel, CarouselContent, CarouselItem, CarouselNext, CarouselPrevious } from './ui/carousel';
 import AudioProgressBar from './AudioProgressBar';
 import { Button } from './ui/button';
-import { Equal, Pause, Play, SkipBack, SkipForward, X } from 'lucide-react';
+import { Equal, Pause, Play, Repeat, Repeat1, Shuffle, SkipBack, SkipForward, X } from 'lucide-react';
 import { Switch } from './ui/switch';
 import { Tooltip, TooltipContent, TooltipProvider, TooltipTrigger } from './ui/tooltip';
 import { exit } from '@tauri-apps/plugin-process';
-import { getCurrentWindow } from '@tauri-apps/api/window';
 import { Session, SessionControl } from '@/types/winrt';
 import React from 'react';
 import OverflowingText from './OverflowingText';
 
 type Props = {
   sessions: Session[];
-  controlSession: (source: string, control: SessionControl) => void;
+  controlSession: (sessionId: number, control: SessionControl) => void;
+  alwaysOnTop: boolean;
+  setAlwaysOnTop: (alwaysOnTop: boolean) => void;
 };
 
-const NormalMode = ({ sessions, controlSession }: Props) => {
-  const setAlwaysOnTop = async (alwaysOnTop: boolean) => {
-    const currentWindow = getCurrentWindow();
-    try {
-      await currentWindow.setAlwaysOnTop(alwaysOnTop);
-    } catch {
-      console.error('Failed to set window always on top.');
-    }
-  };
-
+const NormalMode = ({ sessions, controlSession, alwaysOnTop, setAlwaysOnTop }: Props) => {
   const closeApp = () => {
     exit(1);
   };
@@ -39,7 +31,7 @@ const NormalMode = ({ sessions, controlSession }: Props) => {
       <TooltipProvider delayDuration={300}>
         <Tooltip>
           <TooltipTrigger className="float-end">
-            <Switch className="pr-[20px]" onCheckedChange={setAlwaysOnTop} />
+            <Switch className="pr-[20px]" checked={alwaysOnTop} onCheckedChange={setAlwaysOnTop} />
           </TooltipTrigger>
           <TooltipContent>
             <p>Set Always on Top</p>
@@ -49,7 +41,11 @@ const NormalMode = ({ sessions, controlSession }: Props) => {
       <Carousel className="mt-[20px] mx-10" opts={{ loop: true }}>
         <CarouselContent>
           {sessions.map((s, i) => (
-            <CarouselItem key={i}>
+            <CarouselItem
+              key={i}
+              className="rounded-md transition-colors"
+              style={{ backgroundColor: s.palette?.dominant, color: s.palette?.foreground }}
+            >
               <div className="h-2/5 flex">
                 <img className="mx-auto h-full object-contain" src={s.imageUrl} />
               </div>
@@ -58,17 +54,36 @@ const NormalMode = ({ sessions, controlSession }: Props) => {
                 text={s.session?.media?.title || ''}
                 twClass="inline-block relative font-semibold text-lg"
               />
-              <AudioProgressBar rawTimeline={s.session?.timeline} playbackStatus={s.session?.playback?.status} />
+              <AudioProgressBar
+                sessionId={s.sessionId}
+                onSeek={(position) => controlSession(s.sessionId, { SeekTo: position })}
+              />
               <div className="w-full flex justify-between">
-                <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'SkipPrevious')}>
+                <Button
+                  variant="ghost"
+                  size="icon"
+                  className={s.session?.playback?.shuffle ? '' : 'text-gray-500'}
+                  onClick={() => controlSession(s.sessionId, 'ToggleShuffle')}
+                >
+                  <Shuffle />
+                </Button>
+                <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'SkipPrevious')}>
                   <SkipBack />
                 </Button>
-                <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'TogglePlayPause')}>
+                <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'TogglePlayPause')}>
                   {s.session?.playback?.status === 'Playing' ? <Pause /> : <Play />}
                 </Button>
-                <Button variant="ghost" size="icon" onClick={() => controlSession(s.source, 'SkipNext')}>
+                <Button variant="ghost" size="icon" onClick={() => controlSession(s.sessionId, 'SkipNext')}>
                   <SkipForward />
                 </Button>
+                <Button
+                  variant="ghost"
+                  size="icon"
+                  className={s.session?.playback?.autoRepeat === 'None' ? 'text-gray-500' : ''}
+                  onClick={() => controlSession(s.sessionId, 'CycleRepeat')}
+                >
+                  {s.session?.playback?.autoRepeat === 'Track' ? <Repeat1 /> : <Repeat />}
+                </Button>
               </div>
             </CarouselItem>
           ))}
@@ -1,27 +1,42 @@
-export type NowPlaying = {
-  title: string;
-  artist: string;
-  album: string;
-  thumbnail: Iterable<number>;
-  guid: string;
-};
-
-export type Timeline = {
-  positionLoop?: NodeJS.Timeout;
-  length: number;
-  positionMs: number;
+export type SessionPosition = {
+  sessionId: number;
+  position: number;
+  start: number;
+  end: number;
+  rate: number;
+  playing: boolean;
 };
 
 export type Session = {
   source: string;
-  sessionId?: string;
+  sessionId: number;
   session?: SessionModel;
-  image?: Iterable<number>;
+  artId?: string;
+  palette?: Palette;
   imageUrl?: string;
+  miniImageUrl?: string;
+};
+
+export type Palette = {
+  dominant: string;
+  accent: string;
+  foreground: string;
+};
+
+export type Thumbnail = {
+  size: number;
+  mimeType: string;
+  data: Iterable<number>;
+};
+
+export type Artwork = {
+  artId: string;
+  thumbnails: Thumbnail[];
+  palette?: Palette;
 };
 
 export type BaseSessionInfo = {
-  sessionId: string;
+  sessionId: number;
 };
 
 export type SessionCreate = BaseSessionInfo & {
@@ -39,12 +54,14 @@ export type PlaybackType = 'Unknown' | 'Music' | 'Video' | 'Image';
 
 export type PlaybackModel = {
   status: 'Closed' | 'Opened' | 'Changing' | 'Stopped' | 'Playing' | 'Paused';
-  'r#type': PlaybackType;
+  type: PlaybackType;
   rate: number;
   shuffle: boolean;
-  autoRepeat: 'None' | 'Track' | 'List';
+  autoRepeat: AutoRepeatMode;
 };
 
+export type AutoRepeatMode = 'None' | 'Track' | 'List';
+
 export type TimelineModel = {
   start: number;
   end: number;
@@ -71,7 +88,8 @@ export type AlbumModel = {
 export type SessionUpdate = BaseSessionInfo & {
   source: string;
   sessionModel: SessionModel;
-  image?: Iterable<number>;
+  artId?: string;
+  palette?: Palette;
 };
 
 export type SessionRemove = BaseSessionInfo;
@@ -85,8 +103,121 @@ export type SessionControl =
   | 'FastForward'
   | 'Rewind'
   | 'SkipNext'
-  | 'SkipPrevious';
+  | 'SkipPrevious'
+  | 'ToggleShuffle'
+  | 'CycleRepeat'
+  // positions and offsets are in 100ns ticks, like TimelineModel
+  | { SeekTo: number }
+  | { SeekBy: number }
+  | { SetShuffle: boolean }
+  | { SetRepeat: AutoRepeatMode }
+  | { SetRate: number };
+
+export type HistoryEntry = {
+  source: string;
+  title: string;
+  artist: string;
+  album: string | null;
+  durationMs: number | null;
+  startedAtMs: number;
+  endedAtMs: number;
+  playedMs: number;
+};
+
+export type HistoryRange = {
+  fromMs?: number;
+  toMs?: number;
+};
+
+export type HotkeyAction = SessionControl | 'ToggleWindow' | 'ToggleMini' | 'CopyNowPlaying';
+
+export type HotkeyBinding = {
+  shortcut: string;
+  action: HotkeyAction;
+  error: string | null;
+};
+
+export type HotkeyConfig = {
+  bindings: Record<string, HotkeyAction>;
+  pin?: string;
+  copyFormat: string;
+};
+
+export type WindowSettings = {
+  normalWidth: number;
+  normalHeight: number;
+  miniWidth: number;
+  miniHeight: number;
+  miniModeEdge: number;
+  alwaysOnTop: boolean;
+};
+
+export type ThumbnailFit = 'Crop' | 'Letterbox';
+
+// sizes are physical pixels, see thumbnail.rs
+export type ThumbnailOptions = {
+  miniSize: number;
+  normalSize: number;
+  fit: ThumbnailFit;
+};
+
+// the settings file holds the credentials, the app only says whether they are set
+export type ApiView = {
+  port: number;
+  hasToken: boolean;
+};
+
+export type ScrobbleView = {
+  lastfm: boolean;
+  listenbrainz: boolean;
+};
+
+export type OutputFile = {
+  name: string;
+  template: string;
+};
+
+export type OutputConfig = {
+  dir: string;
+  files: OutputFile[];
+  placeholder?: string;
+  idleWhenPaused: boolean;
+};
+
+export type DiscordConfig = {
+  clientId: string;
+  allow: string[];
+  deny: string[];
+  details: string;
+  state: string;
+  largeText: string;
+  largeImage: string;
+};
+
+// a service is off while its config is missing
+export type Settings = {
+  version: number;
+  window: WindowSettings;
+  hotkeys: HotkeyConfig;
+  thumbnails: ThumbnailOptions;
+  api?: ApiView;
+  scrobble: ScrobbleView;
+  output?: OutputConfig;
+  discord?: DiscordConfig;
+};
+
+export type MediaErrorKind =
+  | 'NotFound'
+  | 'ControlRejected'
+  | 'BackendUnavailable'
+  | 'ConversionFailed'
+  | 'ThumbnailFailed'
+  | 'StorageFailed'
+  | 'ServiceFailed';
 
-export type WinRTError = {
+export type MediaError = {
+  kind: MediaErrorKind;
   message: string;
+  sessionId: number | null;
+  cause: string | null;
 };